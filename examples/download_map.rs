@@ -1,14 +1,18 @@
-use sayobot_api_wrap::static_resources::RequestBuilder;
-use std::time::Duration;
 use sayobot_api_wrap::resource_type::ResourceType;
+use sayobot_api_wrap::SayobotClient;
+use std::time::Duration;
 
 #[tokio::main]
 async fn main() {
-    let builder = RequestBuilder::new()
+    let client = SayobotClient::builder()
+        .set_download_timeout(Duration::from_secs(30))
+        .build()
+        .unwrap();
+    let builder = client
+        .download()
         .set_sid(2045169)
         .set_resource_type(ResourceType::FullSizeMap)
         .set_download_path("./")
-        .unwrap()
-        .set_timeout(Duration::from_secs(30));
+        .unwrap();
     builder.do_request().await.unwrap();
 }
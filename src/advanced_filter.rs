@@ -0,0 +1,243 @@
+use std::fmt;
+use std::str::FromStr;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::beatmap_params::Range;
+use crate::error::{Result, SayobotError};
+
+/// Typed form of the search api's advanced filter, the `R` query parameter.
+///
+/// On the wire it is a comma separated list of `key:start~end` ranges closed by
+/// `end`, e.g. `star:4~6,ar:9~10,length:0~180,end`. Ranges on keys this type
+/// does not name are kept in `extra`, so parsing a filter and writing it back
+/// gives the same ranges.
+#[derive(Debug, Clone, Default, PartialEq)]
+pub struct AdvancedFilter {
+    pub star: Option<Range>,
+    pub ar: Option<Range>,
+    pub od: Option<Range>,
+    pub cs: Option<Range>,
+    pub hp: Option<Range>,
+    pub bpm: Option<Range>,
+    /// Drain length in seconds.
+    pub length: Option<Range>,
+    pub extra: Vec<(String, Range)>,
+}
+
+impl AdvancedFilter {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn set_star(mut self, range: Range) -> Self {
+        self.star = Some(range);
+        self
+    }
+    pub fn set_ar(mut self, range: Range) -> Self {
+        self.ar = Some(range);
+        self
+    }
+    pub fn set_od(mut self, range: Range) -> Self {
+        self.od = Some(range);
+        self
+    }
+    pub fn set_cs(mut self, range: Range) -> Self {
+        self.cs = Some(range);
+        self
+    }
+    pub fn set_hp(mut self, range: Range) -> Self {
+        self.hp = Some(range);
+        self
+    }
+    pub fn set_bpm(mut self, range: Range) -> Self {
+        self.bpm = Some(range);
+        self
+    }
+    pub fn set_length(mut self, range: Range) -> Self {
+        self.length = Some(range);
+        self
+    }
+    /// Sets a range on a key without a dedicated field, replacing any earlier one.
+    pub fn set_extra(mut self, key: &str, range: Range) -> Self {
+        self.insert(key, range);
+        self
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ranges().next().is_none()
+    }
+
+    /// Every set range with its wire key, in the order they are written.
+    pub fn ranges(&self) -> impl Iterator<Item = (&str, Range)> {
+        [
+            ("star", self.star),
+            ("ar", self.ar),
+            ("od", self.od),
+            ("cs", self.cs),
+            ("hp", self.hp),
+            ("bpm", self.bpm),
+            ("length", self.length),
+        ]
+        .into_iter()
+        .filter_map(|(key, range)| range.map(|range| (key, range)))
+        .chain(self.extra.iter().map(|(key, range)| (key.as_str(), *range)))
+    }
+
+    pub(crate) fn insert(&mut self, key: &str, range: Range) {
+        let key = key.trim().to_lowercase();
+        let slot = match key.as_str() {
+            "star" => &mut self.star,
+            "ar" => &mut self.ar,
+            "od" => &mut self.od,
+            "cs" => &mut self.cs,
+            "hp" => &mut self.hp,
+            "bpm" => &mut self.bpm,
+            "length" => &mut self.length,
+            _ => {
+                match self.extra.iter_mut().find(|(known, _)| *known == key) {
+                    Some((_, existing)) => *existing = range,
+                    None => self.extra.push((key, range)),
+                }
+                return;
+            }
+        };
+        *slot = Some(range);
+    }
+}
+
+/// Writes the `R` value; an empty filter writes nothing.
+impl fmt::Display for AdvancedFilter {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.is_empty() {
+            return Ok(());
+        }
+        for (key, range) in self.ranges() {
+            write!(f, "{}:{}~{},", key, range.start, range.end)?;
+        }
+        f.write_str("end")
+    }
+}
+
+/// Parses an `R` value. Keys are case-insensitive and the closing `end` is optional.
+impl FromStr for AdvancedFilter {
+    type Err = SayobotError;
+
+    fn from_str(value: &str) -> Result<Self> {
+        let mut filter = AdvancedFilter::default();
+        let mut parts = value
+            .split(',')
+            .map(str::trim)
+            .filter(|part| !part.is_empty());
+        for part in parts.by_ref() {
+            if part.eq_ignore_ascii_case("end") {
+                break;
+            }
+            let (key, range) = part.split_once(':').ok_or_else(|| {
+                SayobotError::invalid_params(format!("expected key:start~end, got {:?}", part))
+            })?;
+            let (start, end) = range.split_once('~').ok_or_else(|| {
+                SayobotError::invalid_params(format!("expected start~end, got {:?}", range))
+            })?;
+            let bound = |text: &str| {
+                text.trim().parse::<f64>().map_err(|err| {
+                    SayobotError::invalid_params(format!(
+                        "bad bound {:?} for {}: {}",
+                        text, key, err
+                    ))
+                })
+            };
+            if key.trim().is_empty() {
+                return Err(SayobotError::invalid_params(format!(
+                    "missing key in {:?}",
+                    part
+                )));
+            }
+            filter.insert(key, Range::new(bound(start)?, bound(end)?)?);
+        }
+        if let Some(rest) = parts.next() {
+            return Err(SayobotError::invalid_params(format!(
+                "unexpected {:?} after end",
+                rest
+            )));
+        }
+        Ok(filter)
+    }
+}
+
+impl Serialize for AdvancedFilter {
+    fn serialize<S: Serializer>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for AdvancedFilter {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> std::result::Result<Self, D::Error> {
+        let value = String::deserialize(deserializer)?;
+        value.parse().map_err(serde::de::Error::custom)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: f64, end: f64) -> Range {
+        Range::new(start, end).unwrap()
+    }
+
+    #[test]
+    fn writes_every_range_once() {
+        let filter = AdvancedFilter::new()
+            .set_star(range(4.0, 6.5))
+            .set_ar(range(9.0, 10.0))
+            .set_od(range(8.0, 10.0))
+            .set_cs(range(3.0, 4.2))
+            .set_hp(range(0.0, 5.0))
+            .set_bpm(range(120.0, 200.0))
+            .set_length(range(0.0, 180.0));
+        assert_eq!(
+            filter.to_string(),
+            "star:4~6.5,ar:9~10,od:8~10,cs:3~4.2,hp:0~5,bpm:120~200,length:0~180,end"
+        );
+        assert_eq!(AdvancedFilter::new().to_string(), "");
+    }
+
+    #[test]
+    fn round_trips() {
+        let filter = AdvancedFilter::new()
+            .set_ar(range(9.3, 10.0))
+            .set_length(range(60.0, 240.0))
+            .set_extra("fav", range(100.0, 9999.0));
+        let parsed: AdvancedFilter = filter.to_string().parse().unwrap();
+        assert_eq!(parsed, filter);
+        assert_eq!(parsed.to_string(), filter.to_string());
+
+        let raw = "star:1~2,OD:5~6,custom:0~1,end";
+        let parsed: AdvancedFilter = raw.parse().unwrap();
+        assert_eq!(parsed.od, Some(range(5.0, 6.0)));
+        assert_eq!(parsed.extra, vec![("custom".to_string(), range(0.0, 1.0))]);
+        assert_eq!(parsed.to_string(), "star:1~2,od:5~6,custom:0~1,end");
+        assert_eq!("".parse::<AdvancedFilter>().unwrap(), AdvancedFilter::new());
+    }
+
+    #[test]
+    fn rejects_malformed_values() {
+        for raw in [
+            "star",
+            "star:1",
+            "star:a~2",
+            ":1~2",
+            "star:3~1",
+            "star:1~2,end,ar:1~2",
+        ] {
+            assert!(
+                matches!(
+                    raw.parse::<AdvancedFilter>(),
+                    Err(SayobotError::InvalidParams(_))
+                ),
+                "{}",
+                raw
+            );
+        }
+    }
+}
@@ -1,28 +1,51 @@
-use std::time::Duration;
+use std::fmt;
+#[cfg(feature = "client")]
+use std::time::{Duration, Instant};
 
-use anyhow::{anyhow, Context, Result};
 use serde::{Deserialize, Serialize};
 use serde_with::skip_serializing_none;
+#[cfg(feature = "client")]
+use tracing::Instrument;
 
+#[cfg(feature = "client")]
+use crate::cache::CacheMode;
+#[cfg(feature = "client")]
+use crate::client::SayobotClient;
+use crate::endpoints::Endpoints;
+use crate::enums::{ApiStatus, BeatmapGenre, BeatmapLanguage, GameMode, RankedStatus};
+use crate::error::{decode_json, Result, SayobotError};
+#[cfg(feature = "chrono")]
+use crate::timestamp::UnixTimestamp;
+#[cfg(feature = "client")]
+use crate::telemetry::{self, Endpoint};
+
+#[cfg(feature = "client")]
 pub struct RequestBuilder {
+    client: SayobotClient,
     params: Request,
     request_timeout: Duration,
+    cache_mode: CacheMode,
 }
 
+#[cfg(feature = "client")]
 impl Default for RequestBuilder {
     fn default() -> Self {
-        RequestBuilder {
-            params: Request::default(),
-            request_timeout: Duration::from_secs(5),
-        }
+        Self::new()
     }
 }
 
+#[cfg(feature = "client")]
 impl RequestBuilder {
+    /// Builder bound to [`SayobotClient::global`].
     pub fn new() -> Self {
+        Self::with_client(SayobotClient::global().clone())
+    }
+    pub(crate) fn with_client(client: SayobotClient) -> Self {
         RequestBuilder {
+            request_timeout: client.request_timeout(),
+            client,
             params: Request::default(),
-            request_timeout: Duration::from_secs(10),
+            cache_mode: CacheMode::default(),
         }
     }
     pub fn set_key(mut self, key: String) -> Self {
@@ -37,47 +60,175 @@ impl RequestBuilder {
         self.params.match_mode = Some(match_mode);
         self
     }
+    /// Sets the key and the match mode that goes with its kind.
+    pub fn set_beatmap_key(mut self, key: BeatmapKey) -> Self {
+        self.params = Request::for_key(&key);
+        self
+    }
+
+    /// Whether to read and/or fill the client's response cache.
+    pub fn set_cache_mode(mut self, cache_mode: CacheMode) -> Self {
+        self.cache_mode = cache_mode;
+        self
+    }
 
     pub async fn do_request(self) -> Result<Response> {
-        let url = self.params.query_url()?;
-        let reqwest_cli = reqwest::Client::new();
-        let reqwest_response = reqwest_cli
-            .get(url)
-            .timeout(self.request_timeout)
-            .send()
-            .await
-            .expect("reqwest fail");
-        let text = reqwest_response
-            .text()
-            .await
-            .expect("reqwest response text fail");
-        serde_json::from_str(&text).with_context(|| "parse error")
+        let span = telemetry::request_span(Endpoint::Info);
+        if let Some(key) = &self.params.key {
+            // a number is only known to be a sid or a bid when the match mode says so
+            match (self.params.match_mode, key.trim().parse::<i64>()) {
+                (Some(1), Ok(sid)) => span.record("sid", sid),
+                (Some(2), Ok(bid)) => span.record("bid", bid),
+                _ => span.record("keyword", key.as_str()),
+            };
+        }
+        let started = Instant::now();
+        let result = self.fetch().instrument(span.clone()).await;
+        telemetry::finish(&span, Endpoint::Info, started, &result);
+        result
     }
+
+    async fn fetch(self) -> Result<Response> {
+        let url = self.params.query_url(self.client.endpoints())?;
+        let cache = self.client.cache();
+        let cache_key = self.params.cache_key();
+        if let (Some(cache), true) = (cache, self.cache_mode.reads()) {
+            if let Some(hit) = cache.info.get(&cache_key) {
+                telemetry::record_cache_hit(Endpoint::Info);
+                return Ok(hit);
+            }
+        }
+        #[cfg(feature = "sqlite")]
+        if self.cache_mode.reads() {
+            let key = self.params.key.clone().unwrap_or_default();
+            let match_mode = self.params.match_mode;
+            let lookup = self
+                .client
+                .with_store(move |store| store.fresh_set(&key, match_mode))
+                .await;
+            match lookup {
+                Some(Ok(Some(data))) => {
+                    telemetry::record_store_hit(Endpoint::Info);
+                    return Ok(Response { data, status: 0 });
+                }
+                Some(Err(err)) => tracing::warn!(error = %err, "metadata store lookup failed"),
+                Some(Ok(None)) | None => {}
+            }
+        }
+
+        let reqwest_response = self.client.get(&url, self.request_timeout).await?;
+        let text = reqwest_response.text().await?;
+        let response = Response::from_json(&text, self.params.key.as_deref().unwrap_or_default())?;
+        #[cfg(feature = "sqlite")]
+        {
+            let data = response.data.clone();
+            let write = self
+                .client
+                .with_store(move |store| store.upsert_set(&data))
+                .await;
+            if let Some(Err(err)) = write {
+                tracing::warn!(sid = response.data.sid, error = %err, "metadata store write failed");
+            }
+        }
+        if let (Some(cache), true) = (cache, self.cache_mode.writes()) {
+            cache.info.insert(cache_key, response.clone());
+        }
+        Ok(response)
+    }
+}
+
+/// Looks up the set with sid `sid`, through [`SayobotClient::global`].
+#[cfg(feature = "client")]
+pub async fn lookup_set(sid: i64) -> Result<BeatmapLookup> {
+    SayobotClient::global().lookup_set(sid).await
+}
+
+/// Looks up the set holding difficulty `bid`, through [`SayobotClient::global`].
+#[cfg(feature = "client")]
+pub async fn lookup_beatmap(bid: i64) -> Result<BeatmapLookup> {
+    SayobotClient::global().lookup_beatmap(bid).await
+}
+
+/// What a beatmap info lookup is keyed by.
+///
+/// | kind   | match mode |
+/// |--------|------------|
+/// | `Sid`  | 1          |
+/// | `Bid`  | 2          |
+/// | `Md5`  | 4          |
+/// | `Text` | not sent, the api guesses |
+#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+pub enum BeatmapKey {
+    Sid(i64),
+    Bid(i64),
+    /// MD5 of a `.osu` file.
+    Md5(String),
+    /// A sid, bid or keyword, as typed by a user.
+    Text(String),
 }
 
+impl BeatmapKey {
+    pub fn match_mode(&self) -> Option<i32> {
+        match self {
+            BeatmapKey::Sid(_) => Some(1),
+            BeatmapKey::Bid(_) => Some(2),
+            BeatmapKey::Md5(_) => Some(4),
+            BeatmapKey::Text(_) => None,
+        }
+    }
+}
+
+/// The key as sent in the query.
+impl fmt::Display for BeatmapKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            BeatmapKey::Sid(id) | BeatmapKey::Bid(id) => write!(f, "{}", id),
+            BeatmapKey::Md5(hash) => f.write_str(&hash.trim().to_lowercase()),
+            BeatmapKey::Text(text) => f.write_str(text),
+        }
+    }
+}
+
+/// Query of a beatmap info lookup.
 #[skip_serializing_none]
-#[derive(Debug, Serialize, Deserialize, Default)]
-struct Request {
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
+pub struct Request {
+    /// Sid, bid or keyword.
     #[serde(rename = "0")]
     #[serde(skip_serializing_if = "Option::is_none")]
-    key: Option<String>,
+    pub key: Option<String>,
     #[serde(skip_serializing_if = "Option::is_none")]
     #[serde(rename = "1")]
-    match_mode: Option<i32>,
+    pub match_mode: Option<i32>,
 }
 
 impl Request {
-    const BASE_URL: &'static str = "https://api.sayobot.cn/v2/beatmapinfo";
-    pub fn query_url(&self) -> Result<String> {
-        let url_params = serde_url_params::to_string(&self)?;
+    pub fn for_key(key: &BeatmapKey) -> Self {
+        Request {
+            key: Some(key.to_string()),
+            match_mode: key.match_mode(),
+        }
+    }
+    pub fn query_url(&self, endpoints: &Endpoints) -> Result<String> {
+        let url_params =
+            serde_url_params::to_string(&self).map_err(SayobotError::invalid_params)?;
         if url_params.is_empty() {
-            return Err(anyhow!("url params is empty"));
+            return Err(SayobotError::invalid_params("url params is empty"));
         }
-        Ok(format!("{}?{}", Request::BASE_URL, url_params))
+        Ok(format!("{}?{}", endpoints.beatmap_info_url(), url_params))
+    }
+    /// Key and match mode, normalised so equivalent lookups share an entry.
+    #[cfg_attr(not(feature = "client"), allow(dead_code))]
+    fn cache_key(&self) -> String {
+        format!(
+            "{}#{}",
+            self.key.as_deref().unwrap_or_default().trim().to_lowercase(),
+            self.match_mode.map(|mode| mode.to_string()).unwrap_or_default()
+        )
     }
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct BuildInfo {
     #[serde(rename = "AR")]
     pub ar: f64,
@@ -112,8 +263,14 @@ pub struct BuildInfo {
     pub version: String,
 }
 
+impl BuildInfo {
+    /// Mode of this difficulty, `None` for a mode id this crate does not know.
+    pub fn game_mode(&self) -> Option<GameMode> {
+        GameMode::from_mode_id(self.mode)
+    }
+}
 
-#[derive(Serialize, Deserialize, Debug)]
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct ResponseData {
     pub approved: Option<i64>,
 
@@ -143,22 +300,122 @@ pub struct ResponseData {
     pub video: i64,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl ResponseData {
+    /// Modes the set has difficulties in, from `bid_data`.
+    pub fn game_modes(&self) -> GameMode {
+        self.bid_data
+            .iter()
+            .filter_map(BuildInfo::game_mode)
+            .fold(GameMode::empty(), |modes, mode| modes | mode)
+    }
+    /// `None` when the api left `approved` out.
+    pub fn ranked_status(&self) -> Option<RankedStatus> {
+        self.approved.map(RankedStatus::from_code)
+    }
+    /// Typed `genre`; unknown ids are kept, not dropped.
+    pub fn beatmap_genre(&self) -> BeatmapGenre {
+        BeatmapGenre::from_id(self.genre)
+    }
+    pub fn beatmap_language(&self) -> BeatmapLanguage {
+        BeatmapLanguage::from_id(self.language)
+    }
+    /// When the set was ranked, approved, qualified or loved; `None` if it never was.
+    #[cfg(feature = "chrono")]
+    pub fn approved_at(&self) -> Option<UnixTimestamp> {
+        self.approved_date.and_then(UnixTimestamp::from_api)
+    }
+    #[cfg(feature = "chrono")]
+    pub fn last_updated_at(&self) -> Option<UnixTimestamp> {
+        UnixTimestamp::from_api(self.last_update)
+    }
+    /// When the mirror last refreshed its copy of the set.
+    #[cfg(feature = "chrono")]
+    pub fn local_updated_at(&self) -> Option<UnixTimestamp> {
+        UnixTimestamp::from_api(self.local_update)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Response {
     pub data: ResponseData,
     pub status: i64,
 }
 
+impl Response {
+    /// Decodes a beatmap info body, mapping non-zero statuses to errors.
+    ///
+    /// `key` is the looked up key, used in the [`SayobotError::NotFound`] message.
+    pub fn from_json(body: &str, key: &str) -> Result<Self> {
+        let raw: RawResponse = decode_json(body)?;
+        raw.into_response(key)
+    }
+    pub fn api_status(&self) -> ApiStatus {
+        ApiStatus::from_code(self.status)
+    }
+}
+
+/// A looked up set and, for a [`BeatmapKey::Bid`] lookup, the difficulty that matched.
+#[derive(Debug, Clone)]
+pub struct BeatmapLookup {
+    pub set: ResponseData,
+    pub matched: Option<BuildInfo>,
+}
+
+impl BeatmapLookup {
+    pub fn new(set: ResponseData, key: &BeatmapKey) -> Self {
+        let matched = match key {
+            BeatmapKey::Bid(bid) => set.bid_data.iter().find(|map| map.bid == *bid).cloned(),
+            _ => None,
+        };
+        Self { set, matched }
+    }
+    /// The matched difficulty, else the first one of the set.
+    pub fn difficulty(&self) -> Option<&BuildInfo> {
+        self.matched.as_ref().or_else(|| self.set.bid_data.first())
+    }
+}
+
+/// Wire shape of the response: `data` is absent whenever `status` is non-zero.
+#[derive(Deserialize)]
+struct RawResponse {
+    data: Option<ResponseData>,
+    status: i64,
+}
+
+impl RawResponse {
+    fn into_response(self, key: &str) -> Result<Response> {
+        match (ApiStatus::from_code(self.status), self.data) {
+            (ApiStatus::Ok, Some(data)) => Ok(Response {
+                data,
+                status: self.status,
+            }),
+            (ApiStatus::Ok | ApiStatus::NotFound, _) => {
+                Err(SayobotError::NotFound(format!("beatmap {}", key)))
+            }
+            (ApiStatus::InvalidParams, _) => Err(SayobotError::invalid_params(
+                "beatmap info parameters rejected by the api",
+            )),
+            (status, _) => Err(SayobotError::ApiStatus { status }),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
+    #[cfg(feature = "client")]
+    use crate::mock_server::MockServer;
 
+    #[cfg(feature = "client")]
     #[tokio::test]
     async fn new_map_test() {
         // get 2035712' info to test.
-        let mut search_params = Request::default();
-        search_params.key = Some("2035712".to_string());
-        let url = Request::query_url(&search_params).unwrap();
+        let server = MockServer::start().await.unwrap();
+        let search_params = Request {
+            key: Some("2035712".to_string()),
+            ..Default::default()
+        };
+        let url = search_params.query_url(&server.endpoints()).unwrap();
         let response = reqwest::get(&url).await.unwrap().text().await.unwrap();
         let search_response: Response = serde_json::from_str(&response).unwrap();
         assert_eq!(search_response.status, 0);
@@ -168,9 +425,98 @@ mod tests {
         );
     }
 
+    #[test]
+    fn missing_data_maps_to_not_found() {
+        assert!(matches!(
+            Response::from_json(r#"{"status":-1}"#, "1"),
+            Err(SayobotError::NotFound(_))
+        ));
+        match Response::from_json(r#"{"status":-7}"#, "1") {
+            Err(SayobotError::ApiStatus { status }) => assert_eq!(status.code(), -7),
+            other => panic!("unexpected result {:?}", other),
+        }
+    }
+
+    #[test]
+    fn decodes_modes_and_ranked_status() {
+        use crate::enums::Class;
+
+        let response = Response::from_json(
+            include_str!("../tests/fixtures/beatmapinfo_2035712.json"),
+            "2035712",
+        )
+        .unwrap();
+        assert_eq!(response.data.game_modes(), GameMode::STD | GameMode::TAIKO);
+        assert_eq!(response.data.ranked_status(), Some(RankedStatus::Loved));
+        assert_eq!(response.data.bid_data[2].game_mode(), Some(GameMode::TAIKO));
+        #[cfg(feature = "chrono")]
+        {
+            let data = &response.data;
+            assert_eq!(data.approved_at().unwrap().seconds(), 1690531200);
+            assert!(data.last_updated_at() < data.local_updated_at());
+        }
+        assert_eq!(
+            format!(
+                "{} / {}",
+                response.data.beatmap_genre(),
+                response.data.beatmap_language()
+            ),
+            "Anime / Japanese"
+        );
+
+        for status in RankedStatus::ALL {
+            assert_eq!(RankedStatus::from_code(status.code()), status);
+            assert!(Class::from(status).statuses().contains(&status));
+        }
+        assert_eq!(
+            Class::RANKED_APPROVED.statuses(),
+            vec![RankedStatus::Ranked, RankedStatus::Approved]
+        );
+        assert_eq!(RankedStatus::from(7), RankedStatus::Unknown(7));
+        assert_eq!(Class::from(RankedStatus::Unknown(7)), Class::empty());
+    }
+
+    #[test]
+    fn genre_and_language_ids_round_trip() {
+        use crate::enums::{Genre, Language};
+
+        for id in -1..=16 {
+            assert_eq!(BeatmapGenre::from_id(id).id(), id);
+            assert_eq!(BeatmapLanguage::from_id(id).id(), id);
+        }
+        assert_eq!(BeatmapGenre::from(8), BeatmapGenre::Unknown(8));
+        assert_eq!(BeatmapGenre::HipHop.to_string(), "Hip Hop");
+        assert_eq!(BeatmapGenre::VideoGame.search_flag(), Some(Genre::VIDEO_GAME));
+        assert_eq!(BeatmapGenre::Jazz.search_flag(), None);
+        assert_eq!(BeatmapLanguage::Other.search_flag(), Some(Language::OTHER));
+        assert_eq!(BeatmapLanguage::Polish.search_flag(), None);
+    }
+
+    #[test]
+    fn cache_key_is_normalised() {
+        let request = |key: &str| Request {
+            key: Some(key.to_string()),
+            match_mode: Some(1),
+        };
+        assert_eq!(request(" ABCDEF ").cache_key(), request("abcdef").cache_key());
+        assert_ne!(
+            request("1").cache_key(),
+            Request {
+                key: Some("1".to_string()),
+                match_mode: None,
+            }
+            .cache_key()
+        );
+    }
+
+    #[cfg(feature = "client")]
     #[tokio::test]
     async fn test_request_builder() {
-        let search_response = RequestBuilder::new()
+        let server = MockServer::start().await.unwrap();
+        let search_response = server
+            .client()
+            .unwrap()
+            .beatmap_info()
             .set_key("2035712".to_string())
             .do_request()
             .await
@@ -181,4 +527,75 @@ mod tests {
             search_response.data.bid_data.len()
         );
     }
+
+    #[test]
+    fn keys_pick_their_match_mode() {
+        let request = Request::for_key(&BeatmapKey::Bid(4231121));
+        assert_eq!(request.key.as_deref(), Some("4231121"));
+        assert_eq!(request.match_mode, Some(2));
+        let request = Request::for_key(&BeatmapKey::Md5(" ABC123 ".to_string()));
+        assert_eq!((request.key.as_deref(), request.match_mode), (Some("abc123"), Some(4)));
+        let url = Request::for_key(&BeatmapKey::Text("kano".to_string()))
+            .query_url(&Endpoints::default())
+            .unwrap();
+        assert!(url.ends_with("?0=kano"), "{}", url);
+    }
+
+    #[cfg(feature = "client")]
+    #[tokio::test]
+    async fn lookups_return_the_matched_difficulty() {
+        let server = MockServer::start().await.unwrap();
+        let client = server.client().unwrap();
+        let by_bid = client.lookup_beatmap(4231121).await.unwrap();
+        assert_eq!(by_bid.set.sid, 2035712);
+        assert_eq!(by_bid.matched.as_ref().unwrap().bid, 4231121);
+        assert_eq!(by_bid.difficulty().unwrap().bid, 4231121);
+
+        let by_sid = client.lookup_set(2035712).await.unwrap();
+        assert!(by_sid.matched.is_none());
+        assert_eq!(by_sid.difficulty().unwrap().bid, 4231120);
+
+        // a sid is not a bid
+        assert!(matches!(
+            client.lookup_beatmap(2035712).await,
+            Err(SayobotError::NotFound(_))
+        ));
+        assert!(server.requests()[0].contains("1=2"), "{}", server.requests()[0]);
+    }
+
+    #[cfg(feature = "client")]
+    #[tokio::test]
+    async fn bid_lookup_without_that_difficulty_is_not_found() {
+        use crate::transport::{FakeTransport, TransportResponse};
+
+        // the api answers with a set that does not hold the requested bid
+        let client = SayobotClient::builder()
+            .set_transport(FakeTransport::new(|_| {
+                Ok(TransportResponse::from_bytes(
+                    200,
+                    include_str!("../tests/fixtures/beatmapinfo_2035712.json"),
+                ))
+            }))
+            .build()
+            .unwrap();
+        assert!(matches!(
+            client.lookup_beatmap(1).await,
+            Err(SayobotError::NotFound(message)) if message == "beatmap 1"
+        ));
+        assert!(client.lookup_set(2035712).await.is_ok());
+    }
+
+    #[cfg(feature = "client")]
+    #[tokio::test]
+    async fn test_request_builder_not_found() {
+        let server = MockServer::start().await.unwrap();
+        let result = server
+            .client()
+            .unwrap()
+            .beatmap_info()
+            .set_key("1".to_string())
+            .do_request()
+            .await;
+        assert!(matches!(result, Err(SayobotError::NotFound(_))));
+    }
 }
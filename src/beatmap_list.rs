@@ -3,10 +3,10 @@
 mod tests {
     use crate::enums::*;
     use crate::beatmap_params::*;
-    use crate::beatmap_request::*;
     use crate::beatmap_response::*;
-    use tokio;
-    use reqwest;
+    #[cfg(feature = "client")]
+    use crate::mock_server::MockServer;
+    #[cfg(feature = "client")]
     use std::time::Duration;
 
     #[test]
@@ -18,39 +18,172 @@ mod tests {
 
     #[test]
     fn test_all_mode() {
-        let mut default_params = SearchParams::default();
-        default_params.mode = Some(GameMode::all().bits());
+        let default_params = SearchParams {
+            mode: Some(GameMode::all().bits()),
+            ..Default::default()
+        };
         assert_eq!(default_params.mode, Some(1 + 2 + 4 + 8));
     }
 
+    #[test]
+    fn params_round_trip_through_the_query_url() {
+        use crate::advanced_filter::AdvancedFilter;
+
+        let search_params = SearchParams {
+            limit: Some(25),
+            offset: Some(50),
+            request_type: Some(RequestType::Search),
+            keyword: Some("ディア スウィート & days".to_string()),
+            mode: Some((GameMode::STD | GameMode::MANIA).bits()),
+            class: Some(Class::LOVED.bits()),
+            filter: Some(
+                AdvancedFilter::new()
+                    .set_star(Range::new(4.0, 6.5).unwrap())
+                    .set_extra("fav", Range::new(10.0, 9999.0).unwrap()),
+            ),
+            ..Default::default()
+        };
+        let url = search_params.query_url().unwrap();
+        let parsed = SearchParams::from_query(&url).unwrap();
+        assert_eq!(parsed.request_type, Some(RequestType::Search));
+        assert_eq!(parsed.keyword, search_params.keyword);
+        assert_eq!(parsed.filter, search_params.filter);
+        assert_eq!((parsed.limit, parsed.offset), (Some(25), Some(50)));
+        assert_eq!(parsed.query_url().unwrap(), url);
+
+        let bare = SearchParams::from_query("T=1&L=10&unknown=x").unwrap();
+        assert_eq!(bare.request_type, Some(RequestType::Hot));
+        assert_eq!(bare.query_string().unwrap(), "L=10&T=1");
+    }
+
+    #[test]
+    fn from_query_rejects_invalid_values() {
+        for query in [
+            "/beatmaplist?T=9",
+            "L=abc",
+            "L=0",
+            "O=-1",
+            "M=16",
+            "R=star:6~4,end",
+        ] {
+            assert!(
+                matches!(
+                    SearchParams::from_query(query),
+                    Err(crate::error::SayobotError::InvalidParams(_))
+                ),
+                "{}",
+                query
+            );
+        }
+    }
+
+    #[test]
+    fn advanced_filter_goes_into_r() {
+        use crate::advanced_filter::AdvancedFilter;
+
+        let search_params = SearchParams {
+            keyword: Some("kano".to_string()),
+            filter: Some(
+                AdvancedFilter::new()
+                    .set_ar(Range::new(9.0, 10.0).unwrap())
+                    .set_od(Range::new(8.0, 10.0).unwrap()),
+            ),
+            ..Default::default()
+        };
+        let url = search_params.query_url().unwrap();
+        assert!(url.ends_with("&R=ar%3A9%7E10%2Cod%3A8%7E10%2Cend"), "{}", url);
+
+        let empty = SearchParams {
+            keyword: Some("kano".to_string()),
+            filter: Some(AdvancedFilter::new()),
+            ..Default::default()
+        };
+        assert!(!empty.query_url().unwrap().contains("R="));
+    }
+
+    #[test]
+    fn game_modes_keep_unknown_bits() {
+        let response: SearchResponse =
+            serde_json::from_str(include_str!("../tests/fixtures/beatmaplist.json")).unwrap();
+        let mut set = response.results()[0].clone();
+        set.modes = 0b1_0001;
+        let modes = set.game_modes().unwrap();
+        assert!(modes.contains(GameMode::STD));
+        assert_eq!(modes.bits(), 0b1_0001);
+        set.modes = -1;
+        assert_eq!(set.game_modes(), None);
+        set.modes = i64::from(u32::MAX) + 1;
+        assert_eq!(set.game_modes(), None);
+    }
+
+    #[test]
+    #[allow(deprecated)]
+    fn deprecated_range_fields_still_reach_r() {
+        use crate::advanced_filter::AdvancedFilter;
+
+        let search_params = SearchParams {
+            keyword: Some("kano".to_string()),
+            other: Some("star:1~2,cs:3~4,end".to_string()),
+            stars: Some(Range::new(4.0, 6.0).unwrap()),
+            filter: Some(AdvancedFilter::new().set_cs(Range::new(4.0, 5.0).unwrap())),
+            ..Default::default()
+        };
+        let url = search_params.query_url().unwrap();
+        assert!(url.ends_with("&R=star%3A4%7E6%2Ccs%3A4%7E5%2Cend"), "{}", url);
+
+        let broken = SearchParams {
+            other: Some("star".to_string()),
+            ..Default::default()
+        };
+        assert!(matches!(
+            broken.query_url(),
+            Err(crate::error::SayobotError::InvalidParams(_))
+        ));
+    }
+
+    #[cfg(feature = "client")]
     #[tokio::test]
     async fn new_map_test() {
-        let mut search_params = SearchParams::default();
-        search_params.request_type = Some(RequestType::New);
-        let url = search_params.query_url().unwrap();
+        let server = MockServer::start().await.unwrap();
+        let search_params = SearchParams {
+            request_type: Some(RequestType::New),
+            ..Default::default()
+        };
+        let url = search_params.query_url_for(&server.endpoints()).unwrap();
         let response = reqwest::get(&url).await.unwrap().text().await.unwrap();
         let search_response: SearchResponse = serde_json::from_str(&response).unwrap();
         assert_eq!(search_response.status, 0);
+        assert!(url.contains("T=2"));
+        assert!(!search_response.is_empty());
     }
 
+    #[cfg(feature = "client")]
     #[tokio::test]
     async fn search_test() {
-        let mut search_params = SearchParams::default();
-        search_params.request_type = Some(RequestType::Search);
-        search_params.keyword = Some("kano".to_string());
-        search_params.limit = Some(20);
+        let server = MockServer::start().await.unwrap();
         let cc = (Class::LOVED | Class::RANKED_APPROVED | Class::QUALIFIED).bits();
-        search_params.class = Some(cc);
-        let url = search_params.query_url().unwrap();
+        let search_params = SearchParams {
+            request_type: Some(RequestType::Search),
+            keyword: Some("kano".to_string()),
+            limit: Some(20),
+            class: Some(cc),
+            ..Default::default()
+        };
+        let url = search_params.query_url_for(&server.endpoints()).unwrap();
         let response = reqwest::get(&url).await.unwrap().text().await.unwrap();
         let search_response: SearchResponse = serde_json::from_str(&response).unwrap();
         assert_eq!(search_response.status, 0);
         println!("{:?}", search_response.data.unwrap());
     }
 
+    #[cfg(feature = "client")]
     #[tokio::test]
     async fn request_builder_test() {
-        let builder = RequestBuilder::new()
+        let server = MockServer::start().await.unwrap();
+        let builder = server
+            .client()
+            .unwrap()
+            .search()
             .set_time_out(Duration::from_secs(2))
             .set_request_type(RequestType::Search)
             .set_mode(GameMode::STD)
@@ -63,4 +196,98 @@ mod tests {
         let resp = builder.do_request().await.unwrap();
         assert_eq!(resp.status, 0);
     }
+
+    #[cfg(feature = "client")]
+    #[tokio::test]
+    async fn request_builder_no_results_test() {
+        let server = MockServer::start().await.unwrap();
+        let resp = server
+            .client()
+            .unwrap()
+            .search()
+            .set_request_type(RequestType::Search)
+            .set_keyword("no such map".to_string())
+            .do_request()
+            .await
+            .unwrap();
+        assert_eq!(resp.api_status(), ApiStatus::NotFound);
+        assert!(resp.is_empty());
+    }
+
+    #[cfg(feature = "client")]
+    #[tokio::test]
+    async fn stream_follows_endid() {
+        use futures::TryStreamExt;
+
+        let server = MockServer::start().await.unwrap();
+        let sets: Vec<Data> = server
+            .client()
+            .unwrap()
+            .search()
+            .set_keyword("kano".to_string())
+            .set_limit(2)
+            .into_stream()
+            .try_collect()
+            .await
+            .unwrap();
+        let sids: Vec<i64> = sets.iter().map(|set| set.sid).collect();
+        assert_eq!(sids, vec![2045169, 2035712, 2051120]);
+        assert_eq!(server.requests().len(), 2);
+
+        let capped: Vec<Data> = server
+            .client()
+            .unwrap()
+            .search()
+            .set_keyword("kano".to_string())
+            .set_limit(2)
+            .set_max_results(1)
+            .into_stream()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(capped.len(), 1);
+        assert_eq!(server.requests().len(), 3);
+    }
+
+    #[cfg(feature = "client")]
+    #[tokio::test]
+    async fn stream_skips_sids_repeated_across_pages() {
+        use crate::client::SayobotClient;
+        use crate::transport::{FakeTransport, TransportResponse};
+        use futures::TryStreamExt;
+
+        let set = |sid: i64| {
+            format!(
+                r#"{{"approved":1,"artist":"a","artistU":"a","creator":"c","favourite_count":0,"lastupdate":0,"modes":1,"order":0,"play_count":0,"sid":{},"title":"t","titleU":"t"}}"#,
+                sid
+            )
+        };
+        let page = |sids: &[i64], endid: i64| {
+            let data: Vec<String> = sids.iter().map(|sid| set(*sid)).collect();
+            Ok(TransportResponse::from_bytes(
+                200,
+                format!(r#"{{"data":[{}],"endid":{},"status":0}}"#, data.join(","), endid),
+            ))
+        };
+        let transport = std::sync::Arc::new(FakeTransport::sequence(vec![
+            page(&[1, 2], 2),
+            page(&[2, 3], 4),
+            page(&[], 0),
+        ]));
+        let client = SayobotClient::builder()
+            .set_transport(transport.clone())
+            .build()
+            .unwrap();
+        let sets: Vec<Data> = client
+            .search()
+            .set_keyword("x".to_string())
+            .into_stream()
+            .try_collect()
+            .await
+            .unwrap();
+        let sids: Vec<i64> = sets.iter().map(|set| set.sid).collect();
+        assert_eq!(sids, vec![1, 2, 3]);
+        let urls: Vec<String> = transport.requests().into_iter().map(|r| r.url).collect();
+        assert!(urls[1].contains("O=2") && urls[2].contains("O=4"), "{:?}", urls);
+    }
 }
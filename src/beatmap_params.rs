@@ -1,9 +1,11 @@
 use serde::{Deserialize, Serialize};
 use serde_with::skip_serializing_none;
-use anyhow::{Result, anyhow};
 
-use super::enums::RequestType;
-#[derive(Debug, Serialize, Deserialize, Clone, Copy)]
+use super::advanced_filter::AdvancedFilter;
+use super::endpoints::Endpoints;
+use super::error::{Result, SayobotError};
+use super::enums::{Class, GameMode, Genre, Language, RequestType, SubType};
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq)]
 pub struct Range {
     pub start: f64,
     pub end: f64,
@@ -13,17 +15,19 @@ impl Range {
     pub fn new(start: f64, end: f64) -> Result<Self> {
         //in this area. range is from 0 to 9999
         if start > end {
-            return Err(anyhow!("start must be less than end"));
+            return Err(SayobotError::invalid_params("start must be less than end"));
         }
         if start < 0.0 || end > 9999.0 {
-            return Err(anyhow!("start and end must be between 0 and 9999"));
+            return Err(SayobotError::invalid_params(
+                "start and end must be between 0 and 9999",
+            ));
         }
         Ok(Self { start, end })
     }
 }
 
 #[skip_serializing_none]
-#[derive(Debug, Serialize, Deserialize, Default)]
+#[derive(Debug, Serialize, Deserialize, Default, Clone)]
 #[serde(rename_all = "camelCase")]
 pub struct SearchParams {
     #[serde(rename = "L")]
@@ -32,7 +36,6 @@ pub struct SearchParams {
     pub offset: Option<i32>,
     #[serde(rename = "T")]
     #[serde(skip_serializing_if = "Option::is_none")]
-    #[serde(skip_deserializing)]
     pub request_type: Option<RequestType>,
     #[serde(rename = "K")]
     pub keyword: Option<String>,
@@ -47,33 +50,136 @@ pub struct SearchParams {
     #[serde(rename = "E")]
     pub language: Option<u32>,
     #[serde(rename = "R")]
-    #[serde(skip_serializing_if = "Option::is_none")]
-    pub other: Option<String>,
+    #[serde(skip_serializing_if = "no_filter")]
+    pub filter: Option<AdvancedFilter>,
 
-    #[serde(skip_serializing)]
+    /// Raw `R` value; parsed into [`SearchParams::filter`] when the query is built.
+    #[deprecated(note = "use `filter`")]
+    #[serde(skip)]
+    pub other: Option<String>,
+    #[deprecated(note = "use `filter` with `AdvancedFilter::set_star`")]
+    #[serde(skip)]
     pub stars: Option<Range>,
-    #[serde(skip_serializing)]
+    #[deprecated(note = "use `filter` with `AdvancedFilter::set_ar`")]
+    #[serde(skip)]
     pub ar: Option<Range>,
-    #[serde(skip_serializing)]
+    #[deprecated(note = "use `filter` with `AdvancedFilter::set_od`")]
+    #[serde(skip)]
     pub od: Option<Range>,
-    #[serde(skip_serializing)]
+    #[deprecated(note = "use `filter` with `AdvancedFilter::set_cs`")]
+    #[serde(skip)]
     pub cs: Option<Range>,
-    #[serde(skip_serializing)]
+    #[deprecated(note = "use `filter` with `AdvancedFilter::set_hp`")]
+    #[serde(skip)]
     pub hp: Option<Range>,
-    #[serde(skip_serializing)]
+    #[deprecated(note = "use `filter` with `AdvancedFilter::set_length`")]
+    #[serde(skip)]
     pub length: Option<Range>,
-    #[serde(skip_serializing)]
+    #[deprecated(note = "use `filter` with `AdvancedFilter::set_bpm`")]
+    #[serde(skip)]
     pub bpm: Option<Range>,
 }
 
+fn no_filter(filter: &Option<AdvancedFilter>) -> bool {
+    filter.as_ref().is_none_or(AdvancedFilter::is_empty)
+}
+
 impl SearchParams {
     pub fn query_url(&self) -> Result<String> {
+        self.query_url_for(&Endpoints::default())
+    }
+    pub fn query_url_for(&self, endpoints: &Endpoints) -> Result<String> {
+        Ok(format!("{}?{}", endpoints.beatmap_list_url(), self.query_string()?))
+    }
+    /// The encoded parameters alone, without the `/beatmaplist?` prefix.
+    pub fn query_string(&self) -> Result<String> {
+        let mut params = self.clone();
+        params.filter = self.merged_filter()?;
         let url_params =
-            serde_url_params::to_string(&self).expect("SearchParams serialize error");
+            serde_url_params::to_string(&params).map_err(SayobotError::invalid_params)?;
         if url_params.is_empty() {
-            return Err(anyhow!("url params is empty"));
+            return Err(SayobotError::invalid_params("url params is empty"));
+        }
+        Ok(url_params)
+    }
+
+    /// Decodes the parameters of a `/beatmaplist` request, the inverse of
+    /// [`SearchParams::query_url`].
+    ///
+    /// Takes a full url, a path with a query, or the bare query string. Unknown
+    /// parameters are ignored; the result is checked with [`SearchParams::validate`].
+    pub fn from_query(query: &str) -> Result<Self> {
+        let query = match query.split_once('?') {
+            Some((_, query)) => query,
+            None => query,
+        };
+        let query = query.split('#').next().unwrap_or_default();
+        let params: SearchParams =
+            serde_urlencoded::from_str(query).map_err(SayobotError::invalid_params)?;
+        params.validate()?;
+        Ok(params)
+    }
+
+    /// `filter` with the deprecated `other` and range fields folded in.
+    ///
+    /// `other` is applied first, then the range fields, then `filter`, so the
+    /// newer field wins where both set the same key.
+    #[allow(deprecated)]
+    fn merged_filter(&self) -> Result<Option<AdvancedFilter>> {
+        let shims = [
+            ("star", self.stars),
+            ("ar", self.ar),
+            ("od", self.od),
+            ("cs", self.cs),
+            ("hp", self.hp),
+            ("length", self.length),
+            ("bpm", self.bpm),
+        ];
+        if self.other.is_none() && shims.iter().all(|(_, range)| range.is_none()) {
+            return Ok(self.filter.clone());
+        }
+        let mut merged = match &self.other {
+            Some(other) => other.parse()?,
+            None => AdvancedFilter::default(),
+        };
+        for (key, range) in shims {
+            if let Some(range) = range {
+                merged.insert(key, range);
+            }
+        }
+        if let Some(filter) = &self.filter {
+            for (key, range) in filter.ranges() {
+                merged.insert(key, range);
+            }
+        }
+        Ok(Some(merged))
+    }
+
+    /// Rejects values the api cannot make sense of: negative paging and flag
+    /// bits that no [`GameMode`], [`Class`], [`Genre`], [`Language`] or
+    /// [`SubType`] defines.
+    pub fn validate(&self) -> Result<()> {
+        if self.limit.is_some_and(|limit| limit <= 0) {
+            return Err(SayobotError::invalid_params("L must be positive"));
+        }
+        if self.offset.is_some_and(|offset| offset < 0) {
+            return Err(SayobotError::invalid_params("O must not be negative"));
+        }
+        let flags = [
+            ("S", self.sub_type, SubType::all().bits()),
+            ("M", self.mode, GameMode::all().bits()),
+            ("C", self.class, Class::all().bits()),
+            ("G", self.genre, Genre::all().bits()),
+            ("E", self.language, Language::all().bits()),
+        ];
+        for (name, bits, known) in flags {
+            if let Some(bits) = bits.filter(|bits| bits & !known != 0) {
+                return Err(SayobotError::invalid_params(format!(
+                    "{}={} sets unknown flags",
+                    name, bits
+                )));
+            }
         }
-        Ok(format!("{}?{}", Self::BASE_URL, url_params))
+        Ok(())
     }
-    const BASE_URL: &'static str = "https://api.sayobot.cn/beatmaplist";
 }
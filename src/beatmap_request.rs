@@ -1,32 +1,52 @@
-use std::time::Duration;
+use std::collections::{HashSet, VecDeque};
+use std::time::{Duration, Instant};
 
-use anyhow::{anyhow, Result};
-use reqwest;
+use futures::stream::{self, Stream};
+use tracing::Instrument;
 
-use super::{beatmap_params::{SearchParams, Range}, beatmap_response::SearchResponse, enums::{Class, GameMode, Genre, Language, SubType, RequestType}};
 
+use super::{beatmap_params::{SearchParams, Range}, beatmap_response::{Data, SearchResponse}, enums::{ApiStatus, Class, GameMode, Genre, Language, SubType, RequestType}};
+use crate::advanced_filter::AdvancedFilter;
+use crate::cache::CacheMode;
+use crate::client::SayobotClient;
+use crate::error::{decode_json, Result, SayobotError};
+use crate::telemetry::{self, Endpoint};
 
+
+#[derive(Clone)]
 pub struct RequestBuilder {
+    client: SayobotClient,
     params: SearchParams,
     request_timeout: Duration,
+    cache_mode: CacheMode,
+    max_results: Option<usize>,
 }
 
 impl Default for RequestBuilder {
     fn default() -> Self {
-        Self {
-            params: SearchParams::default(),
-            request_timeout: Duration::from_secs(5),
-        }
+        Self::new()
     }
 }
 
 impl RequestBuilder {
+    /// Builder bound to [`SayobotClient::global`].
     pub fn new() -> Self {
+        Self::with_client(SayobotClient::global().clone())
+    }
+    pub(crate) fn with_client(client: SayobotClient) -> Self {
         Self {
+            request_timeout: client.request_timeout(),
+            client,
             params: SearchParams::default(),
-            request_timeout: Duration::from_secs(5),
+            cache_mode: CacheMode::default(),
+            max_results: None,
         }
     }
+    /// Replaces every search parameter at once.
+    pub fn set_params(mut self, params: SearchParams) -> Self {
+        self.params = params;
+        self
+    }
     pub fn set_request_type(mut self, request_type: RequestType) -> Self {
         self.params.request_type = Some(request_type);
         self
@@ -63,92 +83,159 @@ impl RequestBuilder {
         self.params.language = Some(language.bits());
         self
     }
+    /// Replaces the whole advanced filter (`R`).
+    pub fn set_filter(mut self, filter: AdvancedFilter) -> Self {
+        self.params.filter = Some(filter);
+        self
+    }
+    fn filter_mut(&mut self) -> &mut AdvancedFilter {
+        self.params.filter.get_or_insert_with(AdvancedFilter::default)
+    }
     pub fn set_starts_range(mut self, range: Range) -> Self {
-        self.params.stars = Some(range);
+        self.filter_mut().star = Some(range);
         self
     }
     pub fn set_ar_range(mut self, range: Range) -> Self {
-        self.params.ar = Some(range);
+        self.filter_mut().ar = Some(range);
         self
     }
     pub fn set_od_range(mut self, range: Range) -> Self {
-        self.params.od = Some(range);
+        self.filter_mut().od = Some(range);
         self
     }
     pub fn set_cs_range(mut self, range: Range) -> Self {
-        self.params.cs = Some(range);
+        self.filter_mut().cs = Some(range);
         self
     }
     pub fn set_hp_range(mut self, range: Range) -> Self {
-        self.params.hp = Some(range);
+        self.filter_mut().hp = Some(range);
         self
     }
     pub fn set_bpm_range(mut self, range: Range) -> Self {
-        self.params.bpm = Some(range);
+        self.filter_mut().bpm = Some(range);
         self
     }
     pub fn set_length_range(mut self, range: Range) -> Self {
-        self.params.length = Some(range);
+        self.filter_mut().length = Some(range);
         self
     }
 
-    pub async fn do_request(mut self) -> Result<SearchResponse> {
-        self.build_other_string();
-
-        let request_url = self.params.query_url()?;
-
-        let reqwest_cli = reqwest::Client::new();
-        let resp = reqwest_cli
-            .get(request_url)
-            .timeout(self.request_timeout)
-            .send()
-            .await
-            .expect("reqwest fail");
-
-        if !resp.status().is_success() {
-            return Err(anyhow!("request error: {}", resp.status()));
+    pub async fn do_request(self) -> Result<SearchResponse> {
+        let span = telemetry::request_span(Endpoint::Search);
+        if let Some(keyword) = &self.params.keyword {
+            span.record("keyword", keyword.as_str());
         }
-        if resp.status().is_success() {
-            let resp_text = resp.text().await?;
-            let resp_json: SearchResponse = serde_json::from_str(&resp_text)
-                .expect("parse error");
-            return Ok(resp_json);
-        }
-        Err(anyhow!("other error"))
+        let started = Instant::now();
+        let result = self.fetch().instrument(span.clone()).await;
+        telemetry::finish(&span, Endpoint::Search, started, &result);
+        result
     }
 
-    fn build_other_string(&mut self) {
-        let mut other_value = String::new();
-        if let Some(r) = self.params.stars {
-            other_value += &format!("star:{}~{},", r.start, r.end);
-        }
-        if let Some(r) = self.params.od {
-            other_value += &format!("od:{}~{},", r.start, r.end);
-        }
-        if let Some(r) = self.params.od {
-            other_value += &format!("od:{}~{},", r.start, r.end);
-        }
-        if let Some(r) = self.params.cs {
-            other_value += &format!("cs:{}~{},", r.start, r.end);
-        }
-        if let Some(r) = self.params.hp {
-            other_value += &format!("hp:{}~{},", r.start, r.end);
-        }
-        if let Some(r) = self.params.bpm {
-            other_value += &format!("bpm:{}~{},", r.start, r.end);
+    async fn fetch(self) -> Result<SearchResponse> {
+        let request_url = self.params.query_url_for(self.client.endpoints())?;
+
+        let cache = self.client.cache();
+        if let (Some(cache), true) = (cache, self.cache_mode.reads()) {
+            if let Some(hit) = cache.search.get(&request_url) {
+                telemetry::record_cache_hit(Endpoint::Search);
+                return Ok(hit);
+            }
         }
-        if let Some(r) = self.params.length {
-            other_value += &format!("length:{}~{},", r.start, r.end);
+
+        let resp = self.client.get(&request_url, self.request_timeout).await?;
+        let resp_text = resp.text().await?;
+        let search_response: SearchResponse = decode_json(&resp_text)?;
+        match search_response.api_status() {
+            // "nothing matched" is a normal, empty page for a search
+            ApiStatus::Ok | ApiStatus::NotFound => {}
+            ApiStatus::InvalidParams => {
+                return Err(SayobotError::invalid_params(
+                    "search parameters rejected by the api",
+                ))
+            }
+            status => return Err(SayobotError::ApiStatus { status }),
         }
-        if other_value.is_empty() {
-            self.params.other = None;
-        } else {
-            other_value += "end";
-            self.params.other = Some(other_value);
+        if let (Some(cache), true) = (cache, self.cache_mode.writes()) {
+            cache.search.insert(request_url, search_response.clone());
         }
+        Ok(search_response)
     }
+
     pub fn set_time_out(mut self, timeout: Duration) -> Self {
         self.request_timeout = timeout;
         self
     }
+    /// Whether to read and/or fill the client's response cache.
+    pub fn set_cache_mode(mut self, cache_mode: CacheMode) -> Self {
+        self.cache_mode = cache_mode;
+        self
+    }
+    /// Stops [`RequestBuilder::into_stream`] after this many beatmapsets.
+    pub fn set_max_results(mut self, max_results: usize) -> Self {
+        self.max_results = Some(max_results);
+        self
+    }
+
+    /// Streams every matching beatmapset, requesting further pages as needed.
+    ///
+    /// Pages are followed through `endid` until the api reports no more results
+    /// or the cap from [`RequestBuilder::set_max_results`] is reached. A sid
+    /// already yielded is skipped, so sets shifting across a page boundary are
+    /// not returned twice. The stream ends after the first error.
+    pub fn into_stream(self) -> impl Stream<Item = Result<Data>> {
+        let pages = Pages {
+            max_results: self.max_results,
+            next: Some(self),
+            buffer: VecDeque::new(),
+            seen: HashSet::new(),
+            yielded: 0,
+        };
+        stream::unfold(pages, |mut pages| async move {
+            let item = pages.next_item().await?;
+            Some((item, pages))
+        })
+    }
+}
+
+/// Paging state behind [`RequestBuilder::into_stream`].
+struct Pages {
+    next: Option<RequestBuilder>,
+    buffer: VecDeque<Data>,
+    seen: HashSet<i64>,
+    yielded: usize,
+    max_results: Option<usize>,
+}
+
+impl Pages {
+    async fn next_item(&mut self) -> Option<Result<Data>> {
+        loop {
+            if self.max_results.is_some_and(|max| self.yielded >= max) {
+                return None;
+            }
+            if let Some(data) = self.buffer.pop_front() {
+                self.yielded += 1;
+                return Some(Ok(data));
+            }
+            let builder = self.next.take()?;
+            let offset = builder.params.offset.unwrap_or(0);
+            let page = match builder.clone().do_request().await {
+                Ok(page) => page,
+                Err(err) => return Some(Err(err)),
+            };
+            let endid = page.endid.unwrap_or_default();
+            // an endid that does not move forward means the last page
+            if !page.is_empty() && endid > i64::from(offset) {
+                if let Ok(endid) = i32::try_from(endid) {
+                    let mut builder = builder;
+                    builder.params.offset = Some(endid);
+                    self.next = Some(builder);
+                }
+            }
+            for data in page.data.unwrap_or_default() {
+                if self.seen.insert(data.sid) {
+                    self.buffer.push_back(data);
+                }
+            }
+        }
+    }
 }
@@ -1,6 +1,10 @@
 use serde::{Deserialize, Serialize};
 
-#[derive(Serialize, Deserialize, Debug)]
+use crate::enums::{ApiStatus, GameMode, RankedStatus};
+#[cfg(feature = "chrono")]
+use crate::timestamp::UnixTimestamp;
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct Data {
     pub approved: i64,
     pub artist: String,
@@ -18,7 +22,25 @@ pub struct Data {
     pub title_u: String,
 }
 
-#[derive(Serialize, Deserialize, Debug)]
+impl Data {
+    /// Modes the set has difficulties in, decoded from `modes`.
+    ///
+    /// Bits no [`GameMode`] flag names are kept rather than dropped; `None`
+    /// when `modes` is negative or does not fit in 32 bits. The raw value
+    /// stays available as `modes`.
+    pub fn game_modes(&self) -> Option<GameMode> {
+        u32::try_from(self.modes).ok().map(GameMode::from_bits_retain)
+    }
+    pub fn ranked_status(&self) -> RankedStatus {
+        RankedStatus::from_code(self.approved)
+    }
+    #[cfg(feature = "chrono")]
+    pub fn last_updated_at(&self) -> Option<UnixTimestamp> {
+        UnixTimestamp::from_api(self.lastupdate)
+    }
+}
+
+#[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct SearchResponse {
     pub data: Option<Vec<Data>>,
     pub endid: Option<i64>,
@@ -31,3 +53,16 @@ pub struct SearchResponse {
     pub status: i64,
     pub time_cost: Option<i64>,
 }
+
+impl SearchResponse {
+    pub fn api_status(&self) -> ApiStatus {
+        ApiStatus::from_code(self.status)
+    }
+    /// Beatmapsets on this page; empty when nothing matched.
+    pub fn results(&self) -> &[Data] {
+        self.data.as_deref().unwrap_or_default()
+    }
+    pub fn is_empty(&self) -> bool {
+        self.results().is_empty()
+    }
+}
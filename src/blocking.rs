@@ -0,0 +1,163 @@
+//! Synchronous access to the Sayobot api.
+//!
+//! [`SayobotClient`] wraps the async [`crate::SayobotClient`] and drives every
+//! request on a private single-threaded tokio runtime, so build scripts and small
+//! CLIs can use the api without an async `main`. Parameters and response models
+//! are the same types the async api uses.
+//!
+//! Like `reqwest::blocking`, these methods must not be called from inside an
+//! async runtime; doing so panics.
+
+use std::future::Future;
+use std::path::Path;
+use std::sync::Arc;
+
+use tokio::runtime::{Builder, Runtime};
+
+use crate::beatmap_info_v2;
+use crate::beatmap_params::SearchParams;
+use crate::beatmap_response::SearchResponse;
+use crate::client;
+use crate::error::Result;
+use crate::resource_type::ResourceType;
+
+/// Blocking counterpart of [`crate::SayobotClient`]. Cloning is cheap; clones
+/// share the runtime and the connection pool.
+#[derive(Clone)]
+pub struct SayobotClient {
+    client: client::SayobotClient,
+    runtime: Arc<Runtime>,
+}
+
+impl SayobotClient {
+    /// A blocking client with the default async client configuration.
+    pub fn new() -> Result<Self> {
+        Self::from_async(client::SayobotClient::new())
+    }
+    /// Wraps a client configured through [`crate::SayobotClientBuilder`].
+    pub fn from_async(client: client::SayobotClient) -> Result<Self> {
+        let runtime = Builder::new_current_thread().enable_all().build()?;
+        Ok(Self {
+            client,
+            runtime: Arc::new(runtime),
+        })
+    }
+
+    /// The wrapped async client.
+    pub fn client(&self) -> &client::SayobotClient {
+        &self.client
+    }
+
+    /// Runs a search, e.g. with params built by hand or reused from an async caller.
+    pub fn search(&self, params: SearchParams) -> Result<SearchResponse> {
+        self.block_on(self.client.search().set_params(params).do_request())
+    }
+    /// Looks up a beatmapset by sid, bid or keyword.
+    pub fn beatmap_info(&self, key: &str) -> Result<beatmap_info_v2::Response> {
+        self.block_on(
+            self.client
+                .beatmap_info()
+                .set_key(key.to_string())
+                .do_request(),
+        )
+    }
+    /// See [`client::SayobotClient::lookup_set`].
+    pub fn lookup_set(&self, sid: i64) -> Result<beatmap_info_v2::BeatmapLookup> {
+        self.block_on(self.client.lookup_set(sid))
+    }
+    /// See [`client::SayobotClient::lookup_beatmap`].
+    pub fn lookup_beatmap(&self, bid: i64) -> Result<beatmap_info_v2::BeatmapLookup> {
+        self.block_on(self.client.lookup_beatmap(bid))
+    }
+    /// Downloads a resource into `download_path` and returns the saved file name.
+    pub fn download<P: AsRef<Path>>(
+        &self,
+        sid: i64,
+        resource_type: ResourceType,
+        download_path: P,
+    ) -> Result<String> {
+        let builder = self
+            .client
+            .download()
+            .set_sid(sid)
+            .set_resource_type(resource_type)
+            .set_download_path(download_path)?;
+        self.block_on(builder.do_request())
+    }
+
+    /// Runs any request built from [`SayobotClient::client`] to completion, for
+    /// options the shortcuts above do not expose.
+    pub fn block_on<F: Future>(&self, future: F) -> F::Output {
+        self.runtime.block_on(future)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::cache::CacheMode;
+    use crate::transport::{FakeTransport, TransportResponse};
+
+    const INFO: &str = include_str!("../tests/fixtures/beatmapinfo_2035712.json");
+    const LIST: &str = include_str!("../tests/fixtures/beatmaplist.json");
+
+    fn fake_client() -> (SayobotClient, Arc<FakeTransport>) {
+        let transport = Arc::new(FakeTransport::new(|request| {
+            let response = if request.url.contains("/v2/beatmapinfo") {
+                TransportResponse::from_bytes(200, INFO)
+            } else if request.url.contains("/beatmaplist") {
+                TransportResponse::from_bytes(200, LIST)
+            } else {
+                TransportResponse::from_bytes(200, &b"osz"[..]).with_header(
+                    "content-disposition",
+                    "attachment; filename*=utf-8''2035712%20blocking.osz",
+                )
+            };
+            Ok(response)
+        }));
+        let client = client::SayobotClient::builder()
+            .set_transport(transport.clone())
+            .build()
+            .unwrap();
+        (SayobotClient::from_async(client).unwrap(), transport)
+    }
+
+    #[test]
+    fn blocking_calls_share_the_async_models() {
+        let (client, transport) = fake_client();
+        let params = SearchParams {
+            keyword: Some("kano".to_string()),
+            ..Default::default()
+        };
+        let search = client.search(params).unwrap();
+        assert_eq!(search.results().len(), 5);
+        assert!(transport.requests()[0].url.contains("K=kano"));
+
+        let info = client.beatmap_info("2035712").unwrap();
+        assert_eq!(info.data.sid, 2035712);
+
+        let dir = std::env::temp_dir().join(format!("sayobot-blocking-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        let file_name = client
+            .download(2035712, ResourceType::MiniMap, &dir)
+            .unwrap();
+        assert_eq!(file_name, "2035712 blocking.osz");
+        assert_eq!(std::fs::read(dir.join(&file_name)).unwrap(), b"osz");
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn block_on_runs_configured_builders() {
+        let (client, transport) = fake_client();
+        let request = client
+            .client()
+            .beatmap_info()
+            .set_key("2035712".to_string())
+            .set_cache_mode(CacheMode::Bypass)
+            .do_request();
+        assert_eq!(client.block_on(request).unwrap().data.bids_amount, 3);
+        assert_eq!(transport.requests().len(), 1);
+    }
+}
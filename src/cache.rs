@@ -0,0 +1,221 @@
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+use crate::beatmap_info_v2;
+use crate::beatmap_response::SearchResponse;
+
+/// Sizing and lifetimes of the in-memory response cache.
+#[derive(Debug, Clone)]
+pub struct CacheConfig {
+    capacity: usize,
+    search_ttl: Duration,
+    info_ttl: Duration,
+}
+
+impl Default for CacheConfig {
+    fn default() -> Self {
+        Self {
+            capacity: 1024,
+            search_ttl: Duration::from_secs(60),
+            info_ttl: Duration::from_secs(10 * 60),
+        }
+    }
+}
+
+impl CacheConfig {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// Maximum entries kept per endpoint before the least recently used is evicted.
+    pub fn set_capacity(mut self, capacity: usize) -> Self {
+        self.capacity = capacity.max(1);
+        self
+    }
+    pub fn set_search_ttl(mut self, ttl: Duration) -> Self {
+        self.search_ttl = ttl;
+        self
+    }
+    pub fn set_info_ttl(mut self, ttl: Duration) -> Self {
+        self.info_ttl = ttl;
+        self
+    }
+}
+
+/// How a single request interacts with the client's cache.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum CacheMode {
+    /// Serve from the cache when fresh, store the response otherwise.
+    #[default]
+    Use,
+    /// Neither read nor write the cache.
+    Bypass,
+    /// Always hit the network and store the fresh response.
+    Refresh,
+}
+
+impl CacheMode {
+    pub(crate) fn reads(self) -> bool {
+        self == CacheMode::Use
+    }
+    pub(crate) fn writes(self) -> bool {
+        self != CacheMode::Bypass
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct ResponseCacheStats {
+    pub search: CacheStats,
+    pub info: CacheStats,
+}
+
+pub(crate) struct ResponseCache {
+    pub(crate) search: TtlCache<SearchResponse>,
+    pub(crate) info: TtlCache<beatmap_info_v2::Response>,
+}
+
+impl ResponseCache {
+    pub(crate) fn new(config: &CacheConfig) -> Self {
+        Self {
+            search: TtlCache::new(config.capacity, config.search_ttl),
+            info: TtlCache::new(config.capacity, config.info_ttl),
+        }
+    }
+    pub(crate) fn stats(&self) -> ResponseCacheStats {
+        ResponseCacheStats {
+            search: self.search.stats(),
+            info: self.info.stats(),
+        }
+    }
+}
+
+/// LRU map whose entries also expire after a fixed time to live.
+pub(crate) struct TtlCache<V> {
+    capacity: usize,
+    ttl: Duration,
+    state: Mutex<CacheState<V>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+struct CacheState<V> {
+    entries: HashMap<String, CacheEntry<V>>,
+    tick: u64,
+}
+
+struct CacheEntry<V> {
+    value: V,
+    inserted_at: Instant,
+    last_used: u64,
+}
+
+impl<V: Clone> TtlCache<V> {
+    pub(crate) fn new(capacity: usize, ttl: Duration) -> Self {
+        Self {
+            capacity: capacity.max(1),
+            ttl,
+            state: Mutex::new(CacheState {
+                entries: HashMap::new(),
+                tick: 0,
+            }),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
+        }
+    }
+
+    pub(crate) fn get(&self, key: &str) -> Option<V> {
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        state.tick += 1;
+        let tick = state.tick;
+        let fresh = match state.entries.get_mut(key) {
+            Some(entry) if entry.inserted_at.elapsed() < self.ttl => {
+                entry.last_used = tick;
+                Some(entry.value.clone())
+            }
+            Some(_) => {
+                state.entries.remove(key);
+                None
+            }
+            None => None,
+        };
+        let counter = if fresh.is_some() { &self.hits } else { &self.misses };
+        counter.fetch_add(1, Ordering::Relaxed);
+        fresh
+    }
+
+    pub(crate) fn insert(&self, key: String, value: V) {
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        state.tick += 1;
+        let tick = state.tick;
+        if !state.entries.contains_key(&key) && state.entries.len() >= self.capacity {
+            // a linear scan keeps this dependency free; capacities are small
+            let oldest = state
+                .entries
+                .iter()
+                .min_by_key(|(_, entry)| entry.last_used)
+                .map(|(key, _)| key.clone());
+            if let Some(oldest) = oldest {
+                state.entries.remove(&oldest);
+            }
+        }
+        state.entries.insert(
+            key,
+            CacheEntry {
+                value,
+                inserted_at: Instant::now(),
+                last_used: tick,
+            },
+        );
+    }
+
+    pub(crate) fn stats(&self) -> CacheStats {
+        let state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: state.entries.len(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn evicts_least_recently_used() {
+        let cache = TtlCache::new(2, Duration::from_secs(60));
+        cache.insert("a".to_string(), 1);
+        cache.insert("b".to_string(), 2);
+        assert_eq!(cache.get("a"), Some(1));
+        cache.insert("c".to_string(), 3);
+        assert_eq!(cache.get("b"), None);
+        assert_eq!(cache.get("a"), Some(1));
+        assert_eq!(cache.get("c"), Some(3));
+        let stats = cache.stats();
+        assert_eq!((stats.hits, stats.misses, stats.entries), (3, 1, 2));
+    }
+
+    #[test]
+    fn expired_entries_are_misses() {
+        let cache = TtlCache::new(2, Duration::ZERO);
+        cache.insert("a".to_string(), 1);
+        assert_eq!(cache.get("a"), None);
+        assert_eq!(cache.stats().entries, 0);
+    }
+
+    #[test]
+    fn cache_modes() {
+        assert!(CacheMode::Use.reads() && CacheMode::Use.writes());
+        assert!(!CacheMode::Refresh.reads() && CacheMode::Refresh.writes());
+        assert!(!CacheMode::Bypass.reads() && !CacheMode::Bypass.writes());
+    }
+}
@@ -0,0 +1,430 @@
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+use std::sync::{Arc, Mutex, MutexGuard};
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use bytes::BytesMut;
+use futures::future::BoxFuture;
+use futures::stream::{self, StreamExt};
+use http::header::CONTENT_LENGTH;
+use http::HeaderMap;
+use serde::{Deserialize, Serialize};
+
+use crate::error::{Result, SayobotError};
+use crate::transport::{HttpRequest, Transport, TransportResponse};
+
+/// Bodies larger than this are passed through without being recorded.
+pub const DEFAULT_MAX_BODY: usize = 1024 * 1024;
+
+/// Whether a [`Cassette`] captures live traffic or plays it back.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum CassetteMode {
+    /// Send requests to the network and append every response to the file;
+    /// bodies over the size limit are streamed through and not kept.
+    Record,
+    /// Serve responses from the file; unmatched requests fail.
+    Replay,
+}
+
+/// One recorded request and its response.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Interaction {
+    pub url: String,
+    pub status: u16,
+    pub headers: Vec<(String, String)>,
+    /// Body as text when it is valid UTF-8.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body: Option<String>,
+    /// Body as base64 when it is binary.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub body_base64: Option<String>,
+    /// The body was over the recording limit and is not in the file.
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    pub body_omitted: bool,
+}
+
+impl Interaction {
+    pub fn new(url: &str, status: u16, headers: Vec<(String, String)>, body: &[u8]) -> Self {
+        let (body, body_base64) = match std::str::from_utf8(body) {
+            Ok(text) => (Some(text.to_string()), None),
+            Err(_) => (None, Some(BASE64.encode(body))),
+        };
+        Self {
+            url: url.to_string(),
+            status,
+            headers,
+            body,
+            body_base64,
+            body_omitted: false,
+        }
+    }
+    /// An interaction whose body was too large to record.
+    pub fn without_body(url: &str, status: u16, headers: Vec<(String, String)>) -> Self {
+        Self {
+            body_omitted: true,
+            ..Self::new(url, status, headers, &[])
+        }
+    }
+    pub fn body_bytes(&self) -> Result<Vec<u8>> {
+        if self.body_omitted {
+            return Err(SayobotError::Cassette(format!(
+                "body of {} was over the size limit and not recorded",
+                self.url
+            )));
+        }
+        match (&self.body, &self.body_base64) {
+            (Some(text), _) => Ok(text.as_bytes().to_vec()),
+            (None, Some(encoded)) => BASE64.decode(encoded).map_err(|err| {
+                SayobotError::Cassette(format!("bad body for {}: {}", self.url, err))
+            }),
+            (None, None) => Ok(Vec::new()),
+        }
+    }
+}
+
+#[derive(Default, Serialize, Deserialize)]
+struct CassetteFile {
+    interactions: Vec<Interaction>,
+}
+
+/// HTTP interactions saved to, or served from, a JSON file.
+///
+/// Replay matches requests by full url; repeated urls are served in the
+/// order they were recorded.
+pub struct Cassette {
+    path: PathBuf,
+    mode: CassetteMode,
+    max_body: usize,
+    state: Mutex<CassetteState>,
+    writing: tokio::sync::Mutex<()>,
+}
+
+struct CassetteState {
+    interactions: Vec<Interaction>,
+    used: HashSet<usize>,
+}
+
+impl Cassette {
+    /// Starts an empty recording that is written to `path` after every response.
+    pub fn record<P: AsRef<Path>>(path: P) -> Self {
+        Self::with_interactions(path, CassetteMode::Record, Vec::new())
+    }
+    /// Loads a recording made with [`Cassette::record`].
+    pub fn replay<P: AsRef<Path>>(path: P) -> Result<Self> {
+        let text = std::fs::read_to_string(path.as_ref())?;
+        let file: CassetteFile = crate::error::decode_json(&text)?;
+        Ok(Self::with_interactions(
+            path,
+            CassetteMode::Replay,
+            file.interactions,
+        ))
+    }
+    fn with_interactions<P: AsRef<Path>>(
+        path: P,
+        mode: CassetteMode,
+        interactions: Vec<Interaction>,
+    ) -> Self {
+        Self {
+            path: path.as_ref().to_path_buf(),
+            mode,
+            max_body: DEFAULT_MAX_BODY,
+            state: Mutex::new(CassetteState {
+                interactions,
+                used: HashSet::new(),
+            }),
+            writing: tokio::sync::Mutex::new(()),
+        }
+    }
+    /// Largest body kept when recording, [`DEFAULT_MAX_BODY`] by default.
+    pub fn set_max_body(mut self, bytes: usize) -> Self {
+        self.max_body = bytes;
+        self
+    }
+
+    pub fn mode(&self) -> CassetteMode {
+        self.mode
+    }
+    pub fn interactions(&self) -> Vec<Interaction> {
+        self.state().interactions.clone()
+    }
+
+    /// Appends an interaction and rewrites the cassette file.
+    pub(crate) async fn push(&self, interaction: Interaction) -> Result<()> {
+        // one write at a time, so an older snapshot never lands after a newer one
+        let _writing = self.writing.lock().await;
+        let text = {
+            let mut state = self.state();
+            state.interactions.push(interaction);
+            let file = CassetteFile {
+                interactions: state.interactions.clone(),
+            };
+            serde_json::to_string_pretty(&file)
+                .map_err(|err| SayobotError::Cassette(err.to_string()))?
+        };
+        tokio::fs::write(&self.path, text).await?;
+        Ok(())
+    }
+
+    /// Next unused interaction recorded for `url`.
+    pub(crate) fn take(&self, url: &str) -> Result<Interaction> {
+        let mut state = self.state();
+        let found = state
+            .interactions
+            .iter()
+            .enumerate()
+            .find(|(index, interaction)| interaction.url == url && !state.used.contains(index))
+            .map(|(index, interaction)| (index, interaction.clone()));
+        match found {
+            Some((index, interaction)) => {
+                state.used.insert(index);
+                Ok(interaction)
+            }
+            None => Err(SayobotError::Cassette(format!(
+                "no recorded response for {} in {}",
+                url,
+                self.path.display()
+            ))),
+        }
+    }
+
+    fn state(&self) -> MutexGuard<'_, CassetteState> {
+        self.state
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+/// Wraps the client's transport to record into, or replay from, a cassette.
+pub(crate) struct CassetteTransport {
+    inner: Arc<dyn Transport>,
+    cassette: Cassette,
+}
+
+impl CassetteTransport {
+    pub(crate) fn new(inner: Arc<dyn Transport>, cassette: Cassette) -> Self {
+        Self { inner, cassette }
+    }
+
+    /// Sends `request` and records the response, keeping at most
+    /// `max_body` bytes of body in memory.
+    async fn record(&self, request: HttpRequest) -> Result<TransportResponse> {
+        let url = request.url.clone();
+        let response = self.inner.send(request).await?;
+        let status = response.status;
+        let headers = recorded_headers(&response.headers);
+        let max_body = self.cassette.max_body;
+        let too_long = response
+            .headers
+            .get(CONTENT_LENGTH)
+            .and_then(|len| len.to_str().ok()?.parse::<usize>().ok())
+            .is_some_and(|len| len > max_body);
+        if too_long {
+            let interaction = Interaction::without_body(&url, status.as_u16(), headers);
+            self.cassette.push(interaction).await?;
+            return Ok(response);
+        }
+        let TransportResponse {
+            headers: response_headers,
+            body: mut rest,
+            ..
+        } = response;
+        let mut body = BytesMut::new();
+        while let Some(chunk) = rest.next().await {
+            body.extend_from_slice(&chunk?);
+            if body.len() > max_body {
+                // too large after all: hand back what was read and stream the rest
+                let interaction = Interaction::without_body(&url, status.as_u16(), headers);
+                self.cassette.push(interaction).await?;
+                let head = stream::once(async move { Ok(body.freeze()) });
+                return Ok(TransportResponse {
+                    status,
+                    headers: response_headers,
+                    body: head.chain(rest).boxed(),
+                });
+            }
+        }
+        let body = body.freeze();
+        let interaction = Interaction::new(&url, status.as_u16(), headers, &body);
+        self.cassette.push(interaction).await?;
+        Ok(TransportResponse {
+            status,
+            headers: response_headers,
+            body: stream::once(async move { Ok(body) }).boxed(),
+        })
+    }
+}
+
+impl Transport for CassetteTransport {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<TransportResponse>> {
+        Box::pin(async move {
+            if self.cassette.mode() == CassetteMode::Record {
+                return self.record(request).await;
+            }
+            let interaction = self.cassette.take(&request.url)?;
+            let mut response =
+                TransportResponse::from_bytes(interaction.status, interaction.body_bytes()?);
+            for (name, value) in &interaction.headers {
+                response = response.with_header(name, value);
+            }
+            Ok(response)
+        })
+    }
+}
+
+fn recorded_headers(headers: &HeaderMap) -> Vec<(String, String)> {
+    headers
+        .iter()
+        .filter_map(|(name, value)| Some((name.to_string(), value.to_str().ok()?.to_string())))
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::beatmap_info_v2;
+    use crate::client::SayobotClient;
+    use crate::mock_server::{MockServer, FAKE_OSZ};
+    use crate::resource_type::ResourceType;
+    use crate::transport::FakeTransport;
+    use std::time::Duration;
+
+    const SYNTHETIC: &str = concat!(
+        env!("CARGO_MANIFEST_DIR"),
+        "/tests/fixtures/cassettes/synthetic.json"
+    );
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir =
+            std::env::temp_dir().join(format!("sayobot-cassette-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
+
+    #[test]
+    fn binary_bodies_round_trip() {
+        let interaction = Interaction::new("http://x/a.osz", 200, Vec::new(), FAKE_OSZ);
+        assert!(interaction.body.is_none());
+        assert_eq!(interaction.body_bytes().unwrap(), FAKE_OSZ);
+        let interaction = Interaction::new("http://x/a", 200, Vec::new(), b"{}");
+        assert_eq!(interaction.body.as_deref(), Some("{}"));
+    }
+
+    #[tokio::test]
+    async fn record_then_replay_without_network() {
+        let dir = temp_dir("record-replay");
+        let path = dir.join("cassette.json");
+        let server = MockServer::start().await.unwrap();
+        let recorder = SayobotClient::builder()
+            .set_endpoints(server.endpoints())
+            .set_cassette(Cassette::record(&path))
+            .build()
+            .unwrap();
+        let recorded = recorder
+            .beatmap_info()
+            .set_key("2035712".to_string())
+            .do_request()
+            .await
+            .unwrap();
+        recorder
+            .download()
+            .set_sid(2035712)
+            .set_resource_type(ResourceType::MiniMap)
+            .set_download_path(&dir)
+            .unwrap()
+            .do_request()
+            .await
+            .unwrap();
+        let endpoints = server.endpoints();
+        drop(server);
+
+        let player = SayobotClient::builder()
+            .set_endpoints(endpoints)
+            .set_cassette(Cassette::replay(&path).unwrap())
+            .build()
+            .unwrap();
+        let replayed = player
+            .beatmap_info()
+            .set_key("2035712".to_string())
+            .do_request()
+            .await
+            .unwrap();
+        assert_eq!(replayed.data.title, recorded.data.title);
+        assert!(matches!(
+            player
+                .beatmap_info()
+                .set_key("1".to_string())
+                .do_request()
+                .await,
+            Err(SayobotError::Cassette(_))
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    /// Replays a hand-written cassette; the payloads follow the JSON fixtures
+    /// and were not captured from the live api.
+    #[tokio::test]
+    async fn synthetic_payloads_still_decode() {
+        let client = SayobotClient::builder()
+            .set_cassette(Cassette::replay(SYNTHETIC).unwrap())
+            .build()
+            .unwrap();
+        let search = client
+            .search()
+            .set_keyword("kano".to_string())
+            .do_request()
+            .await
+            .unwrap();
+        assert_eq!(search.status, 0);
+        assert_eq!(search.results().len(), 3);
+        assert_eq!(search.results()[0].sid, 2045169);
+        assert_eq!(search.results()[1].title_u, "ディア・スウィート・デイズ");
+
+        let info: beatmap_info_v2::Response = client
+            .beatmap_info()
+            .set_key("2035712".to_string())
+            .do_request()
+            .await
+            .unwrap();
+        assert_eq!(info.data.sid, 2035712);
+        assert_eq!(info.data.bids_amount, 3);
+        assert_eq!(info.data.bid_data[1].version, "Sweet");
+        assert_eq!(info.data.bid_data[1].ar, 9.0);
+        assert_eq!(info.data.artist_u.as_deref(), Some("鹿乃"));
+    }
+
+    #[tokio::test]
+    async fn large_bodies_stream_through_unrecorded() {
+        let dir = temp_dir("large-bodies");
+        let path = dir.join("cassette.json");
+        let inner = Arc::new(FakeTransport::new(|request| {
+            let response = TransportResponse::from_bytes(200, FAKE_OSZ);
+            Ok(if request.url.ends_with("/sized") {
+                response.with_header("content-length", &FAKE_OSZ.len().to_string())
+            } else {
+                response
+            })
+        }));
+        let transport = CassetteTransport::new(inner, Cassette::record(&path).set_max_body(4));
+        for url in ["http://x/chunked", "http://x/sized"] {
+            let request = HttpRequest::new(url, Duration::from_secs(1));
+            let body = transport
+                .send(request)
+                .await
+                .unwrap()
+                .bytes()
+                .await
+                .unwrap();
+            assert_eq!(body, FAKE_OSZ);
+        }
+        let recorded = transport.cassette.interactions();
+        assert!(recorded.iter().all(|interaction| interaction.body_omitted));
+
+        let replayed = Cassette::replay(&path).unwrap();
+        assert!(matches!(
+            replayed.take("http://x/chunked").unwrap().body_bytes(),
+            Err(SayobotError::Cassette(_))
+        ));
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+}
@@ -0,0 +1,477 @@
+use std::sync::{Arc, OnceLock};
+use std::time::{Duration, Instant};
+
+use bytes::Bytes;
+use futures::stream::{Stream, StreamExt};
+use http::header::{HeaderMap, HeaderName, HeaderValue, RETRY_AFTER};
+use tokio::sync::OwnedSemaphorePermit;
+
+use crate::cache::{CacheConfig, ResponseCache, ResponseCacheStats};
+use crate::cassette::{Cassette, CassetteTransport};
+use crate::endpoints::Endpoints;
+use crate::error::{Result, SayobotError};
+use crate::middleware::Middleware;
+use crate::rate_limit::{Limiter, RateLimit};
+use crate::retry::{parse_retry_after, RetryPolicy};
+use crate::telemetry;
+#[cfg(feature = "sqlite")]
+use crate::store::MetadataStore;
+use crate::transport::{HttpRequest, ReqwestTransport, Transport, TransportResponse};
+use crate::feeds::{Feed, FeedBuilder};
+use crate::{beatmap_info_v2, beatmap_request, static_resources};
+
+/// Shared handle to the Sayobot API.
+///
+/// Cloning is cheap: every clone shares the same connection pool, so keep one
+/// client around and create request builders from it.
+#[derive(Clone)]
+pub struct SayobotClient {
+    inner: Arc<ClientInner>,
+}
+
+struct ClientInner {
+    transport: Arc<dyn Transport>,
+    headers: HeaderMap,
+    middleware: Vec<Arc<dyn Middleware>>,
+    endpoints: Endpoints,
+    retry_policy: RetryPolicy,
+    limiter: Limiter,
+    cache: Option<ResponseCache>,
+    #[cfg(feature = "sqlite")]
+    store: Option<Arc<MetadataStore>>,
+    request_timeout: Duration,
+    download_timeout: Duration,
+}
+
+impl Default for SayobotClient {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl SayobotClient {
+    pub fn new() -> Self {
+        Self {
+            inner: Arc::new(ClientInner {
+                transport: Arc::new(ReqwestTransport::new()),
+                headers: HeaderMap::new(),
+                middleware: Vec::new(),
+                endpoints: Endpoints::default(),
+                retry_policy: RetryPolicy::default(),
+                limiter: Limiter::new(&RateLimit::default()),
+                cache: None,
+                #[cfg(feature = "sqlite")]
+                store: None,
+                request_timeout: DEFAULT_REQUEST_TIMEOUT,
+                download_timeout: DEFAULT_DOWNLOAD_TIMEOUT,
+            }),
+        }
+    }
+    pub fn builder() -> SayobotClientBuilder {
+        SayobotClientBuilder::default()
+    }
+    /// The client used by the free-standing `RequestBuilder::new()` constructors.
+    pub fn global() -> &'static SayobotClient {
+        static GLOBAL: OnceLock<SayobotClient> = OnceLock::new();
+        GLOBAL.get_or_init(SayobotClient::new)
+    }
+
+    pub fn search(&self) -> beatmap_request::RequestBuilder {
+        beatmap_request::RequestBuilder::with_client(self.clone())
+    }
+    pub fn beatmap_info(&self) -> beatmap_info_v2::RequestBuilder {
+        beatmap_info_v2::RequestBuilder::with_client(self.clone())
+    }
+    /// The set with sid `sid`.
+    pub async fn lookup_set(&self, sid: i64) -> Result<beatmap_info_v2::BeatmapLookup> {
+        self.lookup(beatmap_info_v2::BeatmapKey::Sid(sid)).await
+    }
+    /// The set holding difficulty `bid`, with that difficulty as `matched`.
+    ///
+    /// Fails with [`SayobotError::NotFound`] when the returned set has no such
+    /// difficulty.
+    pub async fn lookup_beatmap(&self, bid: i64) -> Result<beatmap_info_v2::BeatmapLookup> {
+        self.lookup(beatmap_info_v2::BeatmapKey::Bid(bid)).await
+    }
+    async fn lookup(
+        &self,
+        key: beatmap_info_v2::BeatmapKey,
+    ) -> Result<beatmap_info_v2::BeatmapLookup> {
+        let response = self
+            .beatmap_info()
+            .set_beatmap_key(key.clone())
+            .do_request()
+            .await?;
+        let lookup = beatmap_info_v2::BeatmapLookup::new(response.data, &key);
+        if let (beatmap_info_v2::BeatmapKey::Bid(bid), None) = (&key, &lookup.matched) {
+            return Err(SayobotError::NotFound(format!("beatmap {}", bid)));
+        }
+        Ok(lookup)
+    }
+    pub fn download(&self) -> static_resources::RequestBuilder {
+        static_resources::RequestBuilder::with_client(self.clone())
+    }
+    /// The hot feed; see [`crate::feeds`].
+    pub fn hot_feed(&self) -> FeedBuilder {
+        FeedBuilder::with_client(self.clone(), Feed::Hot)
+    }
+    /// The new feed; see [`crate::feeds`].
+    pub fn new_feed(&self) -> FeedBuilder {
+        FeedBuilder::with_client(self.clone(), Feed::New)
+    }
+
+    pub fn endpoints(&self) -> &Endpoints {
+        &self.inner.endpoints
+    }
+    pub fn retry_policy(&self) -> &RetryPolicy {
+        &self.inner.retry_policy
+    }
+    /// Hit/miss counters of the response cache, if one is configured.
+    pub fn cache_stats(&self) -> Option<ResponseCacheStats> {
+        self.inner.cache.as_ref().map(ResponseCache::stats)
+    }
+    pub(crate) fn cache(&self) -> Option<&ResponseCache> {
+        self.inner.cache.as_ref()
+    }
+    /// The metadata store every fetched beatmapset is written to, if any.
+    #[cfg(feature = "sqlite")]
+    pub fn store(&self) -> Option<&MetadataStore> {
+        self.inner.store.as_deref()
+    }
+    /// Runs `op` against the store on the blocking thread pool, so SQLite IO
+    /// never stalls a runtime worker; `None` without a store.
+    #[cfg(feature = "sqlite")]
+    pub(crate) async fn with_store<T, F>(&self, op: F) -> Option<Result<T>>
+    where
+        F: FnOnce(&MetadataStore) -> Result<T> + Send + 'static,
+        T: Send + 'static,
+    {
+        let store = self.inner.store.clone()?;
+        Some(
+            match tokio::task::spawn_blocking(move || op(&store)).await {
+                Ok(result) => result,
+                Err(err) if err.is_panic() => std::panic::resume_unwind(err.into_panic()),
+                Err(err) => Err(SayobotError::Io(std::io::Error::other(err))),
+            },
+        )
+    }
+    pub(crate) fn request_timeout(&self) -> Duration {
+        self.inner.request_timeout
+    }
+    pub(crate) fn download_timeout(&self) -> Duration {
+        self.inner.download_timeout
+    }
+
+    /// Sends a GET through the client's middleware and [`Transport`], retrying per
+    /// the client's [`RetryPolicy`] and throttled by its [`RateLimit`].
+    ///
+    /// Only responses with a success status are returned. Retries cover
+    /// everything up to the response headers, not a body that fails mid-stream.
+    pub(crate) async fn get(&self, url: &str, timeout: Duration) -> Result<HttpResponse> {
+        let policy = &self.inner.retry_policy;
+        let mut attempt = 1;
+        loop {
+            let mut request = HttpRequest::new(url, timeout);
+            request.headers = self.inner.headers.clone();
+            for middleware in &self.inner.middleware {
+                middleware.before_send(&mut request)?;
+            }
+            // throttle on the host actually contacted, after any rewrite
+            let permit = self.inner.limiter.acquire(&host_of(&request.url)?).await;
+            let result = self.send(request).await;
+            let status = result.as_ref().ok().map(|response| response.status.as_u16());
+            telemetry::record_attempt(attempt, status);
+            let (err, retry_after) = match result {
+                Ok(response) if response.status.is_success() => {
+                    return Ok(HttpResponse { response, permit })
+                }
+                Ok(response) => {
+                    let retry_after = response
+                        .headers
+                        .get(RETRY_AFTER)
+                        .and_then(|value| value.to_str().ok())
+                        .and_then(parse_retry_after);
+                    let status = response.status.as_u16();
+                    (SayobotError::HttpStatus { status }, retry_after)
+                }
+                Err(err) => (err, None),
+            };
+            // free the host slot so other requests are not stuck behind our backoff
+            drop(permit);
+            if attempt >= policy.max_attempts() || !policy.is_retryable(&err) {
+                return Err(err);
+            }
+            let delay = policy.delay_for(attempt, retry_after);
+            tracing::warn!(
+                url,
+                attempt,
+                delay_ms = delay.as_millis() as u64,
+                error = %err,
+                "retrying sayobot request"
+            );
+            tokio::time::sleep(delay).await;
+            attempt += 1;
+        }
+    }
+
+    /// One attempt, with the after hooks of the middleware applied.
+    async fn send(&self, request: HttpRequest) -> Result<TransportResponse> {
+        let started = Instant::now();
+        let result = self.inner.transport.send(request.clone()).await;
+        let elapsed = started.elapsed();
+        match result {
+            Ok(mut response) => {
+                for middleware in self.inner.middleware.iter().rev() {
+                    middleware.after_receive(&request, &mut response, elapsed);
+                }
+                Ok(response)
+            }
+            Err(err) => {
+                for middleware in self.inner.middleware.iter().rev() {
+                    middleware.on_error(&request, &err, elapsed);
+                }
+                Err(err)
+            }
+        }
+    }
+}
+
+fn host_of(url: &str) -> Result<String> {
+    let host = reqwest::Url::parse(url)
+        .map_err(|err| SayobotError::invalid_params(format!("bad url {}: {}", url, err)))?
+        .host_str()
+        .unwrap_or_default()
+        .to_string();
+    Ok(host)
+}
+
+/// Successful response, holding its concurrency slot until the body is consumed.
+pub(crate) struct HttpResponse {
+    response: TransportResponse,
+    permit: Option<OwnedSemaphorePermit>,
+}
+
+impl HttpResponse {
+    pub(crate) fn headers(&self) -> &HeaderMap {
+        &self.response.headers
+    }
+    pub(crate) async fn text(self) -> Result<String> {
+        let body = self.response.bytes().await?;
+        telemetry::record_bytes(body.len() as u64);
+        String::from_utf8(body.to_vec())
+            .map_err(|err| SayobotError::decode(err, &String::from_utf8_lossy(&body)))
+    }
+    pub(crate) fn bytes_stream(self) -> impl Stream<Item = Result<Bytes>> {
+        let permit = self.permit;
+        self.response.body.map(move |chunk| {
+            let _slot = &permit;
+            chunk
+        })
+    }
+}
+
+const DEFAULT_REQUEST_TIMEOUT: Duration = Duration::from_secs(10);
+const DEFAULT_DOWNLOAD_TIMEOUT: Duration = Duration::from_secs(30);
+
+pub struct SayobotClientBuilder {
+    transport: Option<Arc<dyn Transport>>,
+    middleware: Vec<Arc<dyn Middleware>>,
+    endpoints: Endpoints,
+    retry_policy: RetryPolicy,
+    rate_limit: RateLimit,
+    cache: Option<CacheConfig>,
+    #[cfg(feature = "sqlite")]
+    store: Option<MetadataStore>,
+    cassette: Option<Cassette>,
+    request_timeout: Duration,
+    download_timeout: Duration,
+    headers: HeaderMap,
+}
+
+impl Default for SayobotClientBuilder {
+    fn default() -> Self {
+        Self {
+            transport: None,
+            middleware: Vec::new(),
+            endpoints: Endpoints::default(),
+            retry_policy: RetryPolicy::default(),
+            rate_limit: RateLimit::default(),
+            cache: None,
+            #[cfg(feature = "sqlite")]
+            store: None,
+            cassette: None,
+            request_timeout: DEFAULT_REQUEST_TIMEOUT,
+            download_timeout: DEFAULT_DOWNLOAD_TIMEOUT,
+            headers: HeaderMap::new(),
+        }
+    }
+}
+
+impl SayobotClientBuilder {
+    /// Replaces the default reqwest-based transport.
+    pub fn set_transport<T: Transport + 'static>(mut self, transport: T) -> Self {
+        self.transport = Some(Arc::new(transport));
+        self
+    }
+    /// Appends a middleware to the chain run around every request.
+    pub fn add_middleware<M: Middleware + 'static>(mut self, middleware: M) -> Self {
+        self.middleware.push(Arc::new(middleware));
+        self
+    }
+    pub fn set_endpoints(mut self, endpoints: Endpoints) -> Self {
+        self.endpoints = endpoints;
+        self
+    }
+    /// Retry policy applied to search, beatmap info and download requests.
+    pub fn set_retry_policy(mut self, retry_policy: RetryPolicy) -> Self {
+        self.retry_policy = retry_policy;
+        self
+    }
+    /// Throttling shared by every request made through this client.
+    pub fn set_rate_limit(mut self, rate_limit: RateLimit) -> Self {
+        self.rate_limit = rate_limit;
+        self
+    }
+    /// Enables the in-memory cache for search and beatmap info responses.
+    pub fn set_cache(mut self, cache: CacheConfig) -> Self {
+        self.cache = Some(cache);
+        self
+    }
+    /// Persists every fetched beatmapset into `store`.
+    #[cfg(feature = "sqlite")]
+    pub fn set_store(mut self, store: MetadataStore) -> Self {
+        self.store = Some(store);
+        self
+    }
+    /// Records traffic to, or replays it from, a cassette file.
+    pub fn set_cassette(mut self, cassette: Cassette) -> Self {
+        self.cassette = Some(cassette);
+        self
+    }
+    /// Default timeout for search and beatmap info requests.
+    pub fn set_request_timeout(mut self, timeout: Duration) -> Self {
+        self.request_timeout = timeout;
+        self
+    }
+    /// Default timeout for resource downloads.
+    pub fn set_download_timeout(mut self, timeout: Duration) -> Self {
+        self.download_timeout = timeout;
+        self
+    }
+    /// Adds a header sent with every request made through this client.
+    pub fn set_header(mut self, name: HeaderName, value: HeaderValue) -> Self {
+        self.headers.insert(name, value);
+        self
+    }
+
+    pub fn build(self) -> Result<SayobotClient> {
+        self.rate_limit.validate()?;
+        let mut transport = match self.transport {
+            Some(transport) => transport,
+            None => Arc::new(ReqwestTransport::from_client(
+                reqwest::Client::builder().build()?,
+            )),
+        };
+        if let Some(cassette) = self.cassette {
+            transport = Arc::new(CassetteTransport::new(transport, cassette));
+        }
+        Ok(SayobotClient {
+            inner: Arc::new(ClientInner {
+                transport,
+                headers: self.headers,
+                middleware: self.middleware,
+                endpoints: self.endpoints,
+                retry_policy: self.retry_policy,
+                limiter: Limiter::new(&self.rate_limit),
+                cache: self.cache.as_ref().map(ResponseCache::new),
+                #[cfg(feature = "sqlite")]
+                store: self.store.map(Arc::new),
+                request_timeout: self.request_timeout,
+                download_timeout: self.download_timeout,
+            }),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn clones_share_connection_pool() {
+        let client = SayobotClient::builder()
+            .set_request_timeout(Duration::from_secs(3))
+            .build()
+            .unwrap();
+        let cloned = client.clone();
+        assert!(Arc::ptr_eq(&client.inner, &cloned.inner));
+        assert_eq!(cloned.request_timeout(), Duration::from_secs(3));
+        assert_eq!(cloned.download_timeout(), DEFAULT_DOWNLOAD_TIMEOUT);
+        assert_eq!(cloned.endpoints(), &Endpoints::default());
+    }
+
+    #[test]
+    fn builders_use_client_endpoints() {
+        let client = SayobotClient::builder()
+            .set_endpoints(Endpoints::single_host("http://127.0.0.1:9"))
+            .build()
+            .unwrap();
+        let url = crate::beatmap_params::SearchParams {
+            keyword: Some("kano".to_string()),
+            ..Default::default()
+        }
+        .query_url_for(client.endpoints())
+        .unwrap();
+        assert_eq!(url, "http://127.0.0.1:9/beatmaplist?K=kano");
+    }
+
+    #[tokio::test]
+    async fn backoff_does_not_hold_the_host_slot() {
+        use std::sync::atomic::{AtomicBool, Ordering};
+
+        use crate::transport::FakeTransport;
+
+        let failed_once = AtomicBool::new(false);
+        let transport = FakeTransport::new(move |request| {
+            if request.url.ends_with("/flaky") && !failed_once.swap(true, Ordering::SeqCst) {
+                Ok(TransportResponse::from_bytes(503, "").with_header("Retry-After", "1"))
+            } else {
+                Ok(TransportResponse::from_bytes(200, "{}"))
+            }
+        });
+        let client = SayobotClient::builder()
+            .set_transport(transport)
+            .set_rate_limit(RateLimit::new().set_max_concurrent_per_host(1))
+            .set_retry_policy(RetryPolicy::new().set_jitter(false))
+            .build()
+            .unwrap();
+
+        let flaky = tokio::spawn({
+            let client = client.clone();
+            async move {
+                let response = client.get("http://h/flaky", Duration::from_secs(5)).await?;
+                response.text().await
+            }
+        });
+        // let the first request fail and start its backoff
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let started = Instant::now();
+        let other = client
+            .get("http://h/other", Duration::from_secs(5))
+            .await
+            .unwrap();
+        other.text().await.unwrap();
+        let waited = started.elapsed();
+        assert!(waited < Duration::from_millis(500), "{:?}", waited);
+        assert!(!flaky.is_finished());
+        flaky.await.unwrap().unwrap();
+    }
+
+    #[test]
+    fn global_client_is_shared() {
+        assert!(Arc::ptr_eq(
+            &SayobotClient::global().inner,
+            &SayobotClient::global().inner
+        ));
+    }
+}
@@ -0,0 +1,90 @@
+/// Hosts the crate talks to.
+///
+/// The defaults point at the public Sayobot servers. Override them to target a
+/// mirror, a caching reverse proxy or a local fake server.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Endpoints {
+    /// Base of the JSON API (`/beatmaplist`, `/v2/beatmapinfo`).
+    pub api_base: String,
+    /// Base of the preview assets (covers, preview audio).
+    pub asset_base: String,
+    /// Base of the map and file downloads.
+    pub download_base: String,
+}
+
+impl Default for Endpoints {
+    fn default() -> Self {
+        Self {
+            api_base: Self::SAYOBOT_API.to_string(),
+            asset_base: Self::SAYOBOT_ASSET.to_string(),
+            download_base: Self::SAYOBOT_DOWNLOAD.to_string(),
+        }
+    }
+}
+
+impl Endpoints {
+    const SAYOBOT_API: &'static str = "https://api.sayobot.cn";
+    const SAYOBOT_ASSET: &'static str = "https://a.sayobot.cn";
+    const SAYOBOT_DOWNLOAD: &'static str = "https://dl.sayobot.cn";
+
+    pub fn new(api_base: &str, asset_base: &str, download_base: &str) -> Self {
+        Self {
+            api_base: api_base.to_string(),
+            asset_base: asset_base.to_string(),
+            download_base: download_base.to_string(),
+        }
+    }
+    /// Serves every endpoint from one host, e.g. `http://127.0.0.1:8080`.
+    pub fn single_host(base: &str) -> Self {
+        Self::new(base, base, base)
+    }
+
+    pub fn beatmap_list_url(&self) -> String {
+        format!("{}/beatmaplist", trim(&self.api_base))
+    }
+    pub fn beatmap_info_url(&self) -> String {
+        format!("{}/v2/beatmapinfo", trim(&self.api_base))
+    }
+    pub fn asset_url(&self, path: &str) -> String {
+        format!("{}/{}", trim(&self.asset_base), path.trim_start_matches('/'))
+    }
+    pub fn download_url(&self, path: &str) -> String {
+        format!("{}/{}", trim(&self.download_base), path.trim_start_matches('/'))
+    }
+}
+
+fn trim(base: &str) -> &str {
+    base.trim_end_matches('/')
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn default_points_at_sayobot() {
+        let endpoints = Endpoints::default();
+        assert_eq!(
+            endpoints.beatmap_list_url(),
+            "https://api.sayobot.cn/beatmaplist"
+        );
+        assert_eq!(
+            endpoints.beatmap_info_url(),
+            "https://api.sayobot.cn/v2/beatmapinfo"
+        );
+        assert_eq!(
+            endpoints.download_url("beatmaps/download/mini/1"),
+            "https://dl.sayobot.cn/beatmaps/download/mini/1"
+        );
+    }
+
+    #[test]
+    fn single_host_ignores_trailing_slash() {
+        let endpoints = Endpoints::single_host("http://127.0.0.1:8080/");
+        assert_eq!(endpoints.beatmap_list_url(), "http://127.0.0.1:8080/beatmaplist");
+        assert_eq!(
+            endpoints.asset_url("/preview/1.mp3"),
+            "http://127.0.0.1:8080/preview/1.mp3"
+        );
+    }
+}
@@ -1,7 +1,10 @@
+use std::fmt;
+
 use serde::{Deserialize, Serialize};
 use bitflags::bitflags;
 
 bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
     pub struct Language: u32 {
         const ANY = 0b00000001;
         const OTHER = 0b00000010;
@@ -18,6 +21,7 @@ bitflags! {
     }
 }
 bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
     pub struct Genre: u32 {
         const ANY = 0b00000001;
         const UNSPECIFIED = 0b00000010;
@@ -32,6 +36,7 @@ bitflags! {
     }
 }
 bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
     pub struct Class: u32 {
         const RANKED_APPROVED = 0b0001;
         const QUALIFIED = 0b0010;
@@ -41,6 +46,7 @@ bitflags! {
     }
 }
 bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
      pub struct GameMode: u32 {
         const STD = 0b0001;
         const TAIKO = 0b0010;
@@ -48,7 +54,30 @@ bitflags! {
         const MANIA = 0b1000;
     }
 }
+impl GameMode {
+    /// The mode of a single difficulty, as numbered by osu! (0 std, 1 taiko, 2 ctb, 3 mania).
+    pub fn from_mode_id(id: i64) -> Option<Self> {
+        match id {
+            0 => Some(GameMode::STD),
+            1 => Some(GameMode::TAIKO),
+            2 => Some(GameMode::CTB),
+            3 => Some(GameMode::MANIA),
+            _ => None,
+        }
+    }
+}
+
+impl Class {
+    /// Every ranked status this search class covers.
+    pub fn statuses(self) -> Vec<RankedStatus> {
+        RankedStatus::ALL
+            .into_iter()
+            .filter(|status| self.intersects(Class::from(*status)))
+            .collect()
+    }
+}
 bitflags! {
+    #[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
     pub struct SubType: u32 {
         const TITLE = 0b00000001;
         const ARTIST = 0b00000010;
@@ -59,7 +88,7 @@ bitflags! {
     }
 }
 
-#[derive(Debug, Serialize, Deserialize)]
+#[derive(Debug, Serialize, Deserialize, Clone, Copy, PartialEq, Eq)]
 #[serde(rename_all = "camelCase")]
 pub enum RequestType {
     #[serde(rename = "1")]
@@ -71,3 +100,359 @@ pub enum RequestType {
     #[serde(rename = "4")]
     Search,
 }
+
+/// Value of the `status` field returned by the JSON endpoints.
+///
+/// | code | meaning |
+/// |------|---------|
+/// | 0    | success |
+/// | -1   | nothing matched the request |
+/// | -2   | the request parameters were rejected |
+///
+/// Any other code is kept as [`ApiStatus::Unknown`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ApiStatus {
+    Ok,
+    NotFound,
+    InvalidParams,
+    Unknown(i64),
+}
+
+impl ApiStatus {
+    pub fn from_code(code: i64) -> Self {
+        match code {
+            0 => ApiStatus::Ok,
+            -1 => ApiStatus::NotFound,
+            -2 => ApiStatus::InvalidParams,
+            other => ApiStatus::Unknown(other),
+        }
+    }
+    pub fn code(&self) -> i64 {
+        match self {
+            ApiStatus::Ok => 0,
+            ApiStatus::NotFound => -1,
+            ApiStatus::InvalidParams => -2,
+            ApiStatus::Unknown(code) => *code,
+        }
+    }
+}
+
+impl From<i64> for ApiStatus {
+    fn from(code: i64) -> Self {
+        ApiStatus::from_code(code)
+    }
+}
+
+/// Ranked status of a beatmapset, the `approved` field of the responses.
+///
+/// | code | status    |
+/// |------|-----------|
+/// | -2   | graveyard |
+/// | -1   | WIP       |
+/// | 0    | pending   |
+/// | 1    | ranked    |
+/// | 2    | approved  |
+/// | 3    | qualified |
+/// | 4    | loved     |
+///
+/// Any other code is kept as [`RankedStatus::Unknown`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum RankedStatus {
+    Graveyard,
+    Wip,
+    Pending,
+    Ranked,
+    Approved,
+    Qualified,
+    Loved,
+    Unknown(i64),
+}
+
+impl RankedStatus {
+    /// Every known status, in code order.
+    pub const ALL: [RankedStatus; 7] = [
+        RankedStatus::Graveyard,
+        RankedStatus::Wip,
+        RankedStatus::Pending,
+        RankedStatus::Ranked,
+        RankedStatus::Approved,
+        RankedStatus::Qualified,
+        RankedStatus::Loved,
+    ];
+
+    pub fn from_code(code: i64) -> Self {
+        match code {
+            -2 => RankedStatus::Graveyard,
+            -1 => RankedStatus::Wip,
+            0 => RankedStatus::Pending,
+            1 => RankedStatus::Ranked,
+            2 => RankedStatus::Approved,
+            3 => RankedStatus::Qualified,
+            4 => RankedStatus::Loved,
+            other => RankedStatus::Unknown(other),
+        }
+    }
+    pub fn code(&self) -> i64 {
+        match self {
+            RankedStatus::Graveyard => -2,
+            RankedStatus::Wip => -1,
+            RankedStatus::Pending => 0,
+            RankedStatus::Ranked => 1,
+            RankedStatus::Approved => 2,
+            RankedStatus::Qualified => 3,
+            RankedStatus::Loved => 4,
+            RankedStatus::Unknown(code) => *code,
+        }
+    }
+}
+
+impl From<i64> for RankedStatus {
+    fn from(code: i64) -> Self {
+        RankedStatus::from_code(code)
+    }
+}
+
+/// The search class a status falls under; unknown statuses fall under none.
+impl From<RankedStatus> for Class {
+    fn from(status: RankedStatus) -> Self {
+        match status {
+            RankedStatus::Ranked | RankedStatus::Approved => Class::RANKED_APPROVED,
+            RankedStatus::Qualified => Class::QUALIFIED,
+            RankedStatus::Loved => Class::LOVED,
+            RankedStatus::Pending | RankedStatus::Wip => Class::PENDING_WIP,
+            RankedStatus::Graveyard => Class::GRAVEYARD,
+            RankedStatus::Unknown(_) => Class::empty(),
+        }
+    }
+}
+
+/// Genre of a beatmapset as numbered by osu!, the `genre` field of beatmap info.
+///
+/// Unlike [`Genre`], which selects genres in a search, this is the single
+/// genre of one set. Ids this crate does not know are kept as
+/// [`BeatmapGenre::Unknown`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BeatmapGenre {
+    Any,
+    Unspecified,
+    VideoGame,
+    Anime,
+    Rock,
+    Pop,
+    Other,
+    Novelty,
+    HipHop,
+    Electronic,
+    Metal,
+    Classical,
+    Folk,
+    Jazz,
+    Unknown(i32),
+}
+
+impl BeatmapGenre {
+    pub fn from_id(id: i32) -> Self {
+        match id {
+            0 => BeatmapGenre::Any,
+            1 => BeatmapGenre::Unspecified,
+            2 => BeatmapGenre::VideoGame,
+            3 => BeatmapGenre::Anime,
+            4 => BeatmapGenre::Rock,
+            5 => BeatmapGenre::Pop,
+            6 => BeatmapGenre::Other,
+            7 => BeatmapGenre::Novelty,
+            9 => BeatmapGenre::HipHop,
+            10 => BeatmapGenre::Electronic,
+            11 => BeatmapGenre::Metal,
+            12 => BeatmapGenre::Classical,
+            13 => BeatmapGenre::Folk,
+            14 => BeatmapGenre::Jazz,
+            other => BeatmapGenre::Unknown(other),
+        }
+    }
+    pub fn id(&self) -> i32 {
+        match self {
+            BeatmapGenre::Any => 0,
+            BeatmapGenre::Unspecified => 1,
+            BeatmapGenre::VideoGame => 2,
+            BeatmapGenre::Anime => 3,
+            BeatmapGenre::Rock => 4,
+            BeatmapGenre::Pop => 5,
+            BeatmapGenre::Other => 6,
+            BeatmapGenre::Novelty => 7,
+            BeatmapGenre::HipHop => 9,
+            BeatmapGenre::Electronic => 10,
+            BeatmapGenre::Metal => 11,
+            BeatmapGenre::Classical => 12,
+            BeatmapGenre::Folk => 13,
+            BeatmapGenre::Jazz => 14,
+            BeatmapGenre::Unknown(id) => *id,
+        }
+    }
+    /// Name as shown on the osu! website, `"Unknown"` for unknown ids.
+    pub fn name(&self) -> &'static str {
+        match self {
+            BeatmapGenre::Any => "Any",
+            BeatmapGenre::Unspecified => "Unspecified",
+            BeatmapGenre::VideoGame => "Video Game",
+            BeatmapGenre::Anime => "Anime",
+            BeatmapGenre::Rock => "Rock",
+            BeatmapGenre::Pop => "Pop",
+            BeatmapGenre::Other => "Other",
+            BeatmapGenre::Novelty => "Novelty",
+            BeatmapGenre::HipHop => "Hip Hop",
+            BeatmapGenre::Electronic => "Electronic",
+            BeatmapGenre::Metal => "Metal",
+            BeatmapGenre::Classical => "Classical",
+            BeatmapGenre::Folk => "Folk",
+            BeatmapGenre::Jazz => "Jazz",
+            BeatmapGenre::Unknown(_) => "Unknown",
+        }
+    }
+    /// The search flag selecting this genre, `None` where the search has none.
+    pub fn search_flag(&self) -> Option<Genre> {
+        match self {
+            BeatmapGenre::Any => Some(Genre::ANY),
+            BeatmapGenre::Unspecified => Some(Genre::UNSPECIFIED),
+            BeatmapGenre::VideoGame => Some(Genre::VIDEO_GAME),
+            BeatmapGenre::Anime => Some(Genre::ANIME),
+            BeatmapGenre::Rock => Some(Genre::ROCK),
+            BeatmapGenre::Pop => Some(Genre::POP),
+            BeatmapGenre::Other => Some(Genre::OTHER),
+            BeatmapGenre::Novelty => Some(Genre::NOVELTY),
+            BeatmapGenre::HipHop => Some(Genre::HIPHOP),
+            BeatmapGenre::Electronic => Some(Genre::ELECTRONIC),
+            _ => None,
+        }
+    }
+}
+
+impl From<i32> for BeatmapGenre {
+    fn from(id: i32) -> Self {
+        BeatmapGenre::from_id(id)
+    }
+}
+
+impl fmt::Display for BeatmapGenre {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+/// Language of a beatmapset as numbered by osu!, the `language` field of beatmap info.
+///
+/// The single-set counterpart of the [`Language`] search flags. Ids this crate
+/// does not know are kept as [`BeatmapLanguage::Unknown`].
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum BeatmapLanguage {
+    Any,
+    Unspecified,
+    English,
+    Japanese,
+    Chinese,
+    Instrumental,
+    Korean,
+    French,
+    German,
+    Swedish,
+    Spanish,
+    Italian,
+    Russian,
+    Polish,
+    Other,
+    Unknown(i32),
+}
+
+impl BeatmapLanguage {
+    pub fn from_id(id: i32) -> Self {
+        match id {
+            0 => BeatmapLanguage::Any,
+            1 => BeatmapLanguage::Unspecified,
+            2 => BeatmapLanguage::English,
+            3 => BeatmapLanguage::Japanese,
+            4 => BeatmapLanguage::Chinese,
+            5 => BeatmapLanguage::Instrumental,
+            6 => BeatmapLanguage::Korean,
+            7 => BeatmapLanguage::French,
+            8 => BeatmapLanguage::German,
+            9 => BeatmapLanguage::Swedish,
+            10 => BeatmapLanguage::Spanish,
+            11 => BeatmapLanguage::Italian,
+            12 => BeatmapLanguage::Russian,
+            13 => BeatmapLanguage::Polish,
+            14 => BeatmapLanguage::Other,
+            other => BeatmapLanguage::Unknown(other),
+        }
+    }
+    pub fn id(&self) -> i32 {
+        match self {
+            BeatmapLanguage::Any => 0,
+            BeatmapLanguage::Unspecified => 1,
+            BeatmapLanguage::English => 2,
+            BeatmapLanguage::Japanese => 3,
+            BeatmapLanguage::Chinese => 4,
+            BeatmapLanguage::Instrumental => 5,
+            BeatmapLanguage::Korean => 6,
+            BeatmapLanguage::French => 7,
+            BeatmapLanguage::German => 8,
+            BeatmapLanguage::Swedish => 9,
+            BeatmapLanguage::Spanish => 10,
+            BeatmapLanguage::Italian => 11,
+            BeatmapLanguage::Russian => 12,
+            BeatmapLanguage::Polish => 13,
+            BeatmapLanguage::Other => 14,
+            BeatmapLanguage::Unknown(id) => *id,
+        }
+    }
+    /// Name as shown on the osu! website, `"Unknown"` for unknown ids.
+    pub fn name(&self) -> &'static str {
+        match self {
+            BeatmapLanguage::Any => "Any",
+            BeatmapLanguage::Unspecified => "Unspecified",
+            BeatmapLanguage::English => "English",
+            BeatmapLanguage::Japanese => "Japanese",
+            BeatmapLanguage::Chinese => "Chinese",
+            BeatmapLanguage::Instrumental => "Instrumental",
+            BeatmapLanguage::Korean => "Korean",
+            BeatmapLanguage::French => "French",
+            BeatmapLanguage::German => "German",
+            BeatmapLanguage::Swedish => "Swedish",
+            BeatmapLanguage::Spanish => "Spanish",
+            BeatmapLanguage::Italian => "Italian",
+            BeatmapLanguage::Russian => "Russian",
+            BeatmapLanguage::Polish => "Polish",
+            BeatmapLanguage::Other => "Other",
+            BeatmapLanguage::Unknown(_) => "Unknown",
+        }
+    }
+    /// The search flag selecting this language, `None` where the search has none.
+    pub fn search_flag(&self) -> Option<Language> {
+        match self {
+            BeatmapLanguage::Any => Some(Language::ANY),
+            BeatmapLanguage::English => Some(Language::ENGLISH),
+            BeatmapLanguage::Japanese => Some(Language::JAPANESE),
+            BeatmapLanguage::Chinese => Some(Language::CHINESE),
+            BeatmapLanguage::Instrumental => Some(Language::INSTRUMENTAL),
+            BeatmapLanguage::Korean => Some(Language::KOREAN),
+            BeatmapLanguage::French => Some(Language::FRENCH),
+            BeatmapLanguage::German => Some(Language::GERMAN),
+            BeatmapLanguage::Swedish => Some(Language::SWEDISH),
+            BeatmapLanguage::Spanish => Some(Language::SPANISH),
+            BeatmapLanguage::Italian => Some(Language::ITALIAN),
+            BeatmapLanguage::Other => Some(Language::OTHER),
+            _ => None,
+        }
+    }
+}
+
+impl From<i32> for BeatmapLanguage {
+    fn from(id: i32) -> Self {
+        BeatmapLanguage::from_id(id)
+    }
+}
+
+impl fmt::Display for BeatmapLanguage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(self.name())
+    }
+}
@@ -0,0 +1,88 @@
+use thiserror::Error;
+
+use crate::enums::ApiStatus;
+
+/// Errors returned by every request builder in this crate.
+#[derive(Debug, Error)]
+pub enum SayobotError {
+    /// The request never produced a response (DNS, TLS, connection reset...).
+    #[error("transport error: {0}")]
+    Transport(#[source] BoxError),
+    #[error("request timed out: {0}")]
+    Timeout(#[source] BoxError),
+    /// The server answered with a non-success HTTP status.
+    #[error("http status not success: {status}")]
+    HttpStatus { status: u16 },
+    /// The API answered with a `status` this crate has no better mapping for.
+    #[error("api returned status {}", .status.code())]
+    ApiStatus { status: ApiStatus },
+    /// The response body could not be understood.
+    #[error("failed to decode response: {reason} (body: {snippet:?})")]
+    Decode { reason: String, snippet: String },
+    #[error("not found: {0}")]
+    NotFound(String),
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("invalid parameters: {0}")]
+    InvalidParams(String),
+    /// A cassette could not be replayed, e.g. a request was never recorded.
+    #[error("cassette error: {0}")]
+    Cassette(String),
+    #[cfg(feature = "sqlite")]
+    #[error("metadata store error: {0}")]
+    Store(#[from] rusqlite::Error),
+}
+
+pub type Result<T, E = SayobotError> = std::result::Result<T, E>;
+
+/// Underlying error of a transport failure.
+pub type BoxError = Box<dyn std::error::Error + Send + Sync>;
+
+#[cfg(feature = "client")]
+impl From<reqwest::Error> for SayobotError {
+    fn from(err: reqwest::Error) -> Self {
+        if err.is_timeout() {
+            SayobotError::Timeout(Box::new(err))
+        } else {
+            SayobotError::Transport(Box::new(err))
+        }
+    }
+}
+
+impl SayobotError {
+    const SNIPPET_LEN: usize = 256;
+
+    /// Decode error carrying the beginning of the offending body.
+    pub(crate) fn decode(reason: impl ToString, body: &str) -> Self {
+        SayobotError::Decode {
+            reason: reason.to_string(),
+            snippet: body.chars().take(Self::SNIPPET_LEN).collect(),
+        }
+    }
+    pub(crate) fn invalid_params(reason: impl ToString) -> Self {
+        SayobotError::InvalidParams(reason.to_string())
+    }
+}
+
+/// Parses a JSON body, keeping a snippet of it on failure.
+pub(crate) fn decode_json<T: serde::de::DeserializeOwned>(body: &str) -> Result<T> {
+    serde_json::from_str(body).map_err(|err| SayobotError::decode(err, body))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn decode_error_keeps_short_snippet() {
+        let body = "<html>".repeat(100);
+        let err = decode_json::<serde_json::Value>(&body).unwrap_err();
+        match err {
+            SayobotError::Decode { snippet, .. } => {
+                assert_eq!(snippet.chars().count(), SayobotError::SNIPPET_LEN);
+                assert!(snippet.starts_with("<html>"));
+            }
+            other => panic!("unexpected error {:?}", other),
+        }
+    }
+}
@@ -0,0 +1,231 @@
+//! The hot and new beatmap feeds (`T=1` and `T=2` on `/beatmaplist`).
+//!
+//! ```no_run
+//! # async fn run() -> sayobot_api_wrap::Result<()> {
+//! use sayobot_api_wrap::enums::{Class, GameMode};
+//!
+//! let today = sayobot_api_wrap::feeds::new()
+//!     .set_mode(GameMode::MANIA)
+//!     .set_class(Class::RANKED_APPROVED | Class::LOVED)
+//!     .do_request()
+//!     .await?;
+//! for set in &today.sets {
+//!     println!("{} - {}", set.artist, set.title);
+//! }
+//! # Ok(())
+//! # }
+//! ```
+
+use std::time::Duration;
+
+use futures::stream::{Stream, StreamExt, TryStreamExt};
+
+use crate::beatmap_request;
+use crate::beatmap_response::Data;
+use crate::client::SayobotClient;
+use crate::enums::{Class, GameMode, RequestType};
+use crate::error::Result;
+
+/// Which feed a [`FeedBuilder`] reads.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Feed {
+    /// Most played recently.
+    Hot,
+    /// Most recently updated.
+    New,
+}
+
+impl Feed {
+    fn request_type(self) -> RequestType {
+        match self {
+            Feed::Hot => RequestType::Hot,
+            Feed::New => RequestType::New,
+        }
+    }
+}
+
+/// The hot feed, through [`SayobotClient::global`].
+pub fn hot() -> FeedBuilder {
+    SayobotClient::global().hot_feed()
+}
+
+/// The new feed, through [`SayobotClient::global`].
+pub fn new() -> FeedBuilder {
+    SayobotClient::global().new_feed()
+}
+
+/// One page of a feed, after mode and class filtering.
+#[derive(Debug, Clone)]
+pub struct FeedPage {
+    pub feed: Feed,
+    pub sets: Vec<Data>,
+    /// Offset of the next page, if there is one.
+    pub next_offset: Option<i32>,
+}
+
+/// Reads the hot or new feed.
+///
+/// The mode and class set here are sent to the api and also checked against
+/// every returned set, so only sets with at least one wanted mode and a wanted
+/// ranked status come back even where the api ignores them for a feed.
+#[derive(Clone)]
+pub struct FeedBuilder {
+    feed: Feed,
+    search: beatmap_request::RequestBuilder,
+    mode: Option<GameMode>,
+    class: Option<Class>,
+    max_results: Option<usize>,
+}
+
+impl FeedBuilder {
+    pub(crate) fn with_client(client: SayobotClient, feed: Feed) -> Self {
+        Self {
+            feed,
+            search: client.search().set_request_type(feed.request_type()),
+            mode: None,
+            class: None,
+            max_results: None,
+        }
+    }
+    pub fn set_mode(mut self, mode: GameMode) -> Self {
+        self.search = self.search.set_mode(mode);
+        self.mode = Some(mode);
+        self
+    }
+    pub fn set_class(mut self, class: Class) -> Self {
+        self.search = self.search.set_class(class);
+        self.class = Some(class);
+        self
+    }
+    pub fn set_limit(mut self, limit: i32) -> Self {
+        self.search = self.search.set_limit(limit);
+        self
+    }
+    pub fn set_offset(mut self, offset: i32) -> Self {
+        self.search = self.search.set_offset(offset);
+        self
+    }
+    pub fn set_time_out(mut self, timeout: Duration) -> Self {
+        self.search = self.search.set_time_out(timeout);
+        self
+    }
+    /// Stops [`FeedBuilder::into_stream`] after this many matching sets.
+    pub fn set_max_results(mut self, max_results: usize) -> Self {
+        self.max_results = Some(max_results);
+        self
+    }
+
+    /// Fetches one page; it may hold fewer sets than the limit after filtering.
+    pub async fn do_request(self) -> Result<FeedPage> {
+        let filter = SetFilter {
+            mode: self.mode,
+            class: self.class,
+        };
+        let page = self.search.do_request().await?;
+        let next_offset = page
+            .endid
+            .filter(|endid| *endid > 0)
+            .and_then(|endid| i32::try_from(endid).ok());
+        let sets = page
+            .data
+            .unwrap_or_default()
+            .into_iter()
+            .filter(|set| filter.matches(set))
+            .collect();
+        Ok(FeedPage {
+            feed: self.feed,
+            sets,
+            next_offset,
+        })
+    }
+
+    /// Streams matching sets across pages, see [`beatmap_request::RequestBuilder::into_stream`].
+    pub fn into_stream(self) -> impl Stream<Item = Result<Data>> {
+        let filter = SetFilter {
+            mode: self.mode,
+            class: self.class,
+        };
+        let sets = self
+            .search
+            .into_stream()
+            .try_filter(move |set| std::future::ready(filter.matches(set)));
+        sets.take(self.max_results.unwrap_or(usize::MAX))
+    }
+}
+
+#[derive(Clone, Copy)]
+struct SetFilter {
+    mode: Option<GameMode>,
+    class: Option<Class>,
+}
+
+impl SetFilter {
+    fn matches(&self, set: &Data) -> bool {
+        let mode_ok = self
+            .mode
+            .is_none_or(|mode| set.game_modes().is_some_and(|modes| modes.intersects(mode)));
+        let class_ok = self
+            .class
+            .is_none_or(|class| Class::from(set.ranked_status()).intersects(class));
+        mode_ok && class_ok
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::mock_server::MockServer;
+
+    fn sids(sets: &[Data]) -> Vec<i64> {
+        sets.iter().map(|set| set.sid).collect()
+    }
+
+    #[tokio::test]
+    async fn feeds_set_the_request_type() {
+        let server = MockServer::start().await.unwrap();
+        let client = server.client().unwrap();
+        let hot = client.hot_feed().do_request().await.unwrap();
+        assert_eq!(hot.feed, Feed::Hot);
+        assert_eq!(hot.sets.len(), 5);
+        assert_eq!(hot.next_offset, None);
+        client.new_feed().set_limit(2).do_request().await.unwrap();
+        let requests = server.requests();
+        assert!(requests[0].contains("T=1"), "{}", requests[0]);
+        assert!(requests[1].contains("T=2"), "{}", requests[1]);
+    }
+
+    #[tokio::test]
+    async fn mode_and_class_filter_the_sets() {
+        let server = MockServer::start().await.unwrap();
+        let client = server.client().unwrap();
+        let mania = client
+            .new_feed()
+            .set_mode(GameMode::MANIA)
+            .do_request()
+            .await
+            .unwrap();
+        assert_eq!(sids(&mania.sets), vec![1859243, 1402311]);
+
+        let loved_or_ranked_taiko: Vec<Data> = client
+            .hot_feed()
+            .set_mode(GameMode::TAIKO)
+            .set_class(Class::LOVED | Class::RANKED_APPROVED)
+            .set_limit(1)
+            .into_stream()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(sids(&loved_or_ranked_taiko), vec![2035712]);
+
+        let capped: Vec<Data> = client
+            .new_feed()
+            .set_class(Class::RANKED_APPROVED)
+            .set_limit(1)
+            .set_max_results(2)
+            .into_stream()
+            .try_collect()
+            .await
+            .unwrap();
+        assert_eq!(sids(&capped), vec![2045169, 1859243]);
+    }
+}
@@ -1,8 +1,63 @@
 pub mod enums;
+pub mod advanced_filter;
 pub mod beatmap_list;
 pub mod beatmap_params;
+pub mod query;
 pub mod beatmap_response;
+#[cfg(feature = "client")]
 pub mod beatmap_request;
 pub mod resource_type;
+#[cfg(feature = "client")]
 pub mod static_resources;
 pub mod beatmap_info_v2;
+#[cfg(feature = "blocking")]
+pub mod blocking;
+#[cfg(feature = "client")]
+pub mod cache;
+#[cfg(feature = "client")]
+pub mod cassette;
+#[cfg(feature = "client")]
+pub mod client;
+pub mod endpoints;
+pub mod error;
+#[cfg(feature = "client")]
+pub mod feeds;
+#[cfg(feature = "client")]
+pub mod middleware;
+#[cfg(all(feature = "client", any(test, feature = "test-support")))]
+pub mod mock_server;
+#[cfg(feature = "client")]
+pub mod rate_limit;
+#[cfg(feature = "client")]
+pub mod retry;
+#[cfg(feature = "sqlite")]
+pub mod store;
+#[cfg(feature = "client")]
+pub mod telemetry;
+#[cfg(feature = "chrono")]
+pub mod timestamp;
+#[cfg(feature = "client")]
+pub mod transport;
+
+pub use advanced_filter::AdvancedFilter;
+#[cfg(feature = "client")]
+pub use cache::{CacheConfig, CacheMode};
+#[cfg(feature = "client")]
+pub use cassette::{Cassette, CassetteMode};
+#[cfg(feature = "client")]
+pub use client::{SayobotClient, SayobotClientBuilder};
+pub use endpoints::Endpoints;
+pub use query::QueryError;
+pub use error::{Result, SayobotError};
+#[cfg(feature = "client")]
+pub use middleware::Middleware;
+#[cfg(feature = "client")]
+pub use rate_limit::RateLimit;
+#[cfg(feature = "client")]
+pub use retry::RetryPolicy;
+#[cfg(feature = "sqlite")]
+pub use store::MetadataStore;
+#[cfg(feature = "chrono")]
+pub use timestamp::UnixTimestamp;
+#[cfg(feature = "client")]
+pub use transport::{FakeTransport, ReqwestTransport, Transport};
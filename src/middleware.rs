@@ -0,0 +1,186 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use crate::error::{Result, SayobotError};
+use crate::transport::{HttpRequest, TransportResponse};
+
+/// Hooks run around every request a [`crate::SayobotClient`] sends.
+///
+/// Search, beatmap info and download requests all pass through the client's
+/// middleware chain, once per attempt when a request is retried. `before_send`
+/// runs in the order the middleware was added and may rewrite the url or headers;
+/// the after hooks run in reverse order. `elapsed` is the time until the response
+/// headers arrived, not including the body.
+pub trait Middleware: Send + Sync {
+    /// Called before the request is handed to the transport. An error aborts the
+    /// request without sending it.
+    fn before_send(&self, request: &mut HttpRequest) -> Result<()> {
+        let _ = request;
+        Ok(())
+    }
+    /// Called with every response, including error statuses.
+    fn after_receive(
+        &self,
+        request: &HttpRequest,
+        response: &mut TransportResponse,
+        elapsed: Duration,
+    ) {
+        let _ = (request, response, elapsed);
+    }
+    /// Called when the transport failed to produce a response.
+    fn on_error(&self, request: &HttpRequest, error: &SayobotError, elapsed: Duration) {
+        let _ = (request, error, elapsed);
+    }
+}
+
+impl<T: Middleware + ?Sized> Middleware for Arc<T> {
+    fn before_send(&self, request: &mut HttpRequest) -> Result<()> {
+        (**self).before_send(request)
+    }
+    fn after_receive(
+        &self,
+        request: &HttpRequest,
+        response: &mut TransportResponse,
+        elapsed: Duration,
+    ) {
+        (**self).after_receive(request, response, elapsed)
+    }
+    fn on_error(&self, request: &HttpRequest, error: &SayobotError, elapsed: Duration) {
+        (**self).on_error(request, error, elapsed)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Mutex;
+
+    use http::header::{HeaderValue, USER_AGENT};
+
+    use super::*;
+    use crate::client::SayobotClient;
+    use crate::endpoints::Endpoints;
+    use crate::mock_server::MockServer;
+    use crate::resource_type::ResourceType;
+
+    /// Sends everything to the mock server and tags it with a user agent.
+    struct Rewrite {
+        from: Endpoints,
+        to: Endpoints,
+    }
+
+    impl Middleware for Rewrite {
+        fn before_send(&self, request: &mut HttpRequest) -> Result<()> {
+            for (from, to) in [
+                (&self.from.api_base, &self.to.api_base),
+                (&self.from.asset_base, &self.to.asset_base),
+                (&self.from.download_base, &self.to.download_base),
+            ] {
+                if let Some(rest) = request.url.strip_prefix(from.as_str()) {
+                    request.url = format!("{}{}", to, rest);
+                    break;
+                }
+            }
+            request
+                .headers
+                .insert(USER_AGENT, HeaderValue::from_static("test-bot/1.0"));
+            Ok(())
+        }
+    }
+
+    #[derive(Default)]
+    struct Log {
+        lines: Mutex<Vec<String>>,
+    }
+
+    impl Middleware for Log {
+        fn after_receive(
+            &self,
+            request: &HttpRequest,
+            response: &mut TransportResponse,
+            _elapsed: Duration,
+        ) {
+            let agent = request.headers.get(USER_AGENT).cloned();
+            self.lines.lock().unwrap().push(format!(
+                "{} {} {:?}",
+                response.status.as_u16(),
+                request.url,
+                agent
+            ));
+        }
+    }
+
+    struct Deny;
+
+    impl Middleware for Deny {
+        fn before_send(&self, _request: &mut HttpRequest) -> Result<()> {
+            Err(SayobotError::invalid_params("denied"))
+        }
+    }
+
+    #[tokio::test]
+    async fn middleware_sees_every_endpoint() {
+        let server = MockServer::start().await.unwrap();
+        let log = Arc::new(Log::default());
+        let client = SayobotClient::builder()
+            .add_middleware(Rewrite {
+                from: Endpoints::default(),
+                to: server.endpoints(),
+            })
+            .add_middleware(log.clone())
+            .build()
+            .unwrap();
+        client
+            .search()
+            .set_keyword("kano".to_string())
+            .do_request()
+            .await
+            .unwrap();
+        client
+            .beatmap_info()
+            .set_key("2035712".to_string())
+            .do_request()
+            .await
+            .unwrap();
+        let dir =
+            std::env::temp_dir().join(format!("sayobot-middleware-{}", std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        client
+            .download()
+            .set_sid(2035712)
+            .set_resource_type(ResourceType::MiniMap)
+            .set_download_path(&dir)
+            .unwrap()
+            .do_request()
+            .await
+            .unwrap();
+        std::fs::remove_dir_all(&dir).unwrap();
+
+        let lines = log.lines.lock().unwrap().clone();
+        assert_eq!(lines.len(), 3);
+        for line in &lines {
+            assert!(
+                line.starts_with(&format!("200 {}", server.url())),
+                "{}",
+                line
+            );
+            assert!(line.ends_with("Some(\"test-bot/1.0\")"), "{}", line);
+        }
+    }
+
+    #[tokio::test]
+    async fn before_send_error_aborts_the_request() {
+        let server = MockServer::start().await.unwrap();
+        let client = SayobotClient::builder()
+            .set_endpoints(server.endpoints())
+            .add_middleware(Deny)
+            .build()
+            .unwrap();
+        let result = client
+            .beatmap_info()
+            .set_key("2035712".to_string())
+            .do_request()
+            .await;
+        assert!(matches!(result, Err(SayobotError::InvalidParams(_))));
+        assert!(server.requests().is_empty());
+    }
+}
@@ -0,0 +1,253 @@
+//! Offline stand-in for the Sayobot servers, for tests and CI.
+//!
+//! [`MockServer`] serves recorded JSON for `/beatmaplist` and `/v2/beatmapinfo`
+//! and fake `.osz`/mp3/webp payloads for the download and asset routes, all
+//! from one local port. Point a client at it with [`MockServer::client`].
+
+use std::net::SocketAddr;
+use std::sync::{Arc, Mutex};
+
+use serde_json::{json, Value};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+
+use crate::client::SayobotClient;
+use crate::endpoints::Endpoints;
+use crate::error::Result;
+
+const BEATMAP_LIST: &str = include_str!("../tests/fixtures/beatmaplist.json");
+const BEATMAP_INFO: &str = include_str!("../tests/fixtures/beatmapinfo_2035712.json");
+
+/// Payload served for every `.osz` download.
+pub const FAKE_OSZ: &[u8] = b"PK\x03\x04\x14\x00\xff\xfefake-osz";
+/// Payload served for preview audio and full audio files.
+pub const FAKE_MP3: &[u8] = b"ID3\x04fake-mp3";
+/// Payload served for covers and background images.
+pub const FAKE_WEBP: &[u8] = b"RIFF\x00\x00\x00\x00WEBPfake";
+
+pub struct MockServer {
+    addr: SocketAddr,
+    requests: Arc<Mutex<Vec<String>>>,
+    task: JoinHandle<()>,
+}
+
+impl MockServer {
+    /// Binds to a random local port and starts serving in the background.
+    pub async fn start() -> std::io::Result<Self> {
+        let listener = TcpListener::bind("127.0.0.1:0").await?;
+        let addr = listener.local_addr()?;
+        let requests = Arc::new(Mutex::new(Vec::new()));
+        let log = requests.clone();
+        let task = tokio::spawn(async move {
+            while let Ok((stream, _)) = listener.accept().await {
+                tokio::spawn(serve_connection(stream, log.clone()));
+            }
+        });
+        Ok(Self {
+            addr,
+            requests,
+            task,
+        })
+    }
+
+    /// Base url, e.g. `http://127.0.0.1:41234`.
+    pub fn url(&self) -> String {
+        format!("http://{}", self.addr)
+    }
+    pub fn endpoints(&self) -> Endpoints {
+        Endpoints::single_host(&self.url())
+    }
+    /// A fresh client with every endpoint pointing at this server.
+    pub fn client(&self) -> Result<SayobotClient> {
+        SayobotClient::builder()
+            .set_endpoints(self.endpoints())
+            .build()
+    }
+    /// Request targets (path and query) received so far, in order.
+    pub fn requests(&self) -> Vec<String> {
+        self.requests
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+}
+
+impl Drop for MockServer {
+    fn drop(&mut self) {
+        self.task.abort();
+    }
+}
+
+async fn serve_connection(mut stream: TcpStream, log: Arc<Mutex<Vec<String>>>) {
+    let mut buf = Vec::new();
+    let mut chunk = [0u8; 1024];
+    while !buf.windows(4).any(|window| window == b"\r\n\r\n") {
+        match stream.read(&mut chunk).await {
+            Ok(0) | Err(_) => return,
+            Ok(n) => buf.extend_from_slice(&chunk[..n]),
+        }
+    }
+    let head = String::from_utf8_lossy(&buf);
+    let target = head
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().nth(1))
+        .unwrap_or("/")
+        .to_string();
+    log.lock()
+        .unwrap_or_else(|poisoned| poisoned.into_inner())
+        .push(target.clone());
+
+    let reply = route(&target);
+    let mut response = format!(
+        "HTTP/1.1 {}\r\nContent-Type: {}\r\nContent-Length: {}\r\nConnection: close\r\n",
+        reply.status,
+        reply.content_type,
+        reply.body.len()
+    );
+    if let Some(file_name) = &reply.file_name {
+        response += &format!(
+            "Content-Disposition: attachment; filename=\"{0}\"; filename*=utf-8''{0}\r\n",
+            urlencoding::encode(file_name)
+        );
+    }
+    response += "\r\n";
+    let _ = stream.write_all(response.as_bytes()).await;
+    let _ = stream.write_all(&reply.body).await;
+    let _ = stream.shutdown().await;
+}
+
+struct Reply {
+    status: &'static str,
+    content_type: &'static str,
+    file_name: Option<String>,
+    body: Vec<u8>,
+}
+
+impl Reply {
+    fn json(value: Value) -> Self {
+        Reply {
+            status: "200 OK",
+            content_type: "application/json; charset=utf-8",
+            file_name: None,
+            body: value.to_string().into_bytes(),
+        }
+    }
+    fn file(content_type: &'static str, file_name: String, body: &[u8]) -> Self {
+        Reply {
+            status: "200 OK",
+            content_type,
+            file_name: Some(file_name),
+            body: body.to_vec(),
+        }
+    }
+    fn not_found() -> Self {
+        Reply {
+            status: "404 Not Found",
+            content_type: "text/plain",
+            file_name: None,
+            body: b"not found".to_vec(),
+        }
+    }
+}
+
+fn route(target: &str) -> Reply {
+    let Ok(url) = reqwest::Url::parse(&format!("http://mock{}", target)) else {
+        return Reply::not_found();
+    };
+    let query = |name: &str| {
+        url.query_pairs()
+            .find(|(key, _)| key == name)
+            .map(|(_, value)| value.into_owned())
+    };
+    let segments: Vec<&str> = url.path().trim_matches('/').split('/').collect();
+    match segments.as_slice() {
+        ["beatmaplist"] => Reply::json(beatmap_list(
+            query("K"),
+            query("O").and_then(|offset| offset.parse().ok()).unwrap_or(0),
+            query("L").and_then(|limit| limit.parse().ok()).unwrap_or(20),
+        )),
+        ["v2", "beatmapinfo"] => Reply::json(beatmap_info(
+            &query("0").unwrap_or_default(),
+            query("1").as_deref(),
+        )),
+        ["beatmaps", "download", kind, sid] => {
+            let suffix = match *kind {
+                "full" => "",
+                "novideo" => " [no video]",
+                "mini" => " [mini]",
+                _ => return Reply::not_found(),
+            };
+            let file_name = format!("{} kano - Dear Sweet Days{}.osz", sid, suffix);
+            Reply::file("application/x-osu-beatmap-archive", file_name, FAKE_OSZ)
+        }
+        ["preview", file] if file.ends_with(".mp3") => {
+            Reply::file("audio/mpeg", file.to_string(), FAKE_MP3)
+        }
+        ["beatmaps", sid, "covers", "cover.webp"] => {
+            Reply::file("image/webp", format!("{}.webp", sid), FAKE_WEBP)
+        }
+        ["beatmaps", "files", _sid, file] => {
+            let name = urlencoding::decode(file)
+                .map(|name| name.into_owned())
+                .unwrap_or_else(|_| file.to_string());
+            if name.ends_with(".mp3") {
+                Reply::file("audio/mpeg", name, FAKE_MP3)
+            } else {
+                Reply::file("image/jpeg", name, FAKE_WEBP)
+            }
+        }
+        _ => Reply::not_found(),
+    }
+}
+
+/// Recorded search results filtered by keyword and paged by offset/limit.
+fn beatmap_list(keyword: Option<String>, offset: usize, limit: usize) -> Value {
+    let mut list: Value = serde_json::from_str(BEATMAP_LIST).unwrap_or_default();
+    let all = list["data"].as_array().cloned().unwrap_or_default();
+    let matched: Vec<Value> = match keyword.map(|keyword| keyword.to_lowercase()) {
+        Some(keyword) => all
+            .into_iter()
+            .filter(|item| {
+                ["title", "titleU", "artist", "artistU", "creator"]
+                    .iter()
+                    .filter_map(|field| item[*field].as_str())
+                    .any(|text| text.to_lowercase().contains(&keyword))
+            })
+            .collect(),
+        None => all,
+    };
+    if matched.is_empty() {
+        return json!({ "status": -1 });
+    }
+    let total = matched.len();
+    let page: Vec<Value> = matched.into_iter().skip(offset).take(limit.max(1)).collect();
+    let end = offset + page.len();
+    list["endid"] = json!(if end < total { end } else { 0 });
+    list["results"] = json!(page.len());
+    list["data"] = Value::Array(page);
+    list
+}
+
+/// The recorded set, when `key` is its sid or one of its bids; match mode 1
+/// only accepts the sid and 2 only a bid.
+fn beatmap_info(key: &str, match_mode: Option<&str>) -> Value {
+    let info: Value = serde_json::from_str(BEATMAP_INFO).unwrap_or_default();
+    let id = key.trim().parse::<i64>().ok();
+    let sid_matches = matches!(match_mode, None | Some("1"))
+        && id.is_some()
+        && info["data"]["sid"].as_i64() == id;
+    let bid_matches = matches!(match_mode, None | Some("2"))
+        && id.is_some()
+        && info["data"]["bid_data"]
+            .as_array()
+            .into_iter()
+            .flatten()
+            .any(|map| map["bid"].as_i64() == id);
+    if sid_matches || bid_matches {
+        info
+    } else {
+        json!({ "status": -1 })
+    }
+}
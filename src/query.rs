@@ -0,0 +1,545 @@
+//! A small query language for searches, as typed by chat bot users.
+//!
+//! ```text
+//! kano star:4~6 ar>=9 mode:std status:ranked,loved
+//! ```
+//!
+//! Whitespace separates terms. A term without an operator is part of the
+//! keyword; `"double quotes"` keep characters such as `:` in it. Every other term
+//! is `key` `op` `value`:
+//!
+//! | key                               | value                                         |
+//! |-----------------------------------|-----------------------------------------------|
+//! | `star`, `ar`, `od`, `cs`, `hp`, `bpm`, `length` | a range `4~6`, `4~`, `~6` or a number; also with `>=`, `>`, `<=`, `<` |
+//! | `mode`                            | `std`, `taiko`, `ctb`, `mania`                |
+//! | `status`                          | `ranked`, `qualified`, `loved`, `pending`, `graveyard` |
+//! | `genre`                           | `any`, `unspecified`, `videogame`, `anime`, `rock`, `pop`, `other`, `novelty`, `hiphop`, `electronic` |
+//! | `lang`                            | `any`, `other`, `english`, `japanese`, `chinese`, `instrumental`, `korean`, `french`, `german`, `swedish`, `spanish`, `italian` |
+//! | `in`                              | `title`, `artist`, `creator`, `version`, `tags`, `source` |
+//!
+//! Flag values are comma separated and repeated keys add up. Comparisons narrow
+//! the range set so far, so `ar>=9 ar<=10` is `ar:9~10`; `>` and `<` behave like
+//! `>=` and `<=` since the api only takes inclusive ranges. Any other key that
+//! starts with a letter is an error; a term such as `10:30` whose "key" does not
+//! is part of the keyword.
+//!
+//! [`SearchParams`] parses with [`str::parse`] and prints back in this syntax.
+
+use std::fmt;
+use std::str::FromStr;
+
+use thiserror::Error;
+
+use crate::advanced_filter::AdvancedFilter;
+use crate::beatmap_params::{Range, SearchParams};
+use crate::enums::{Class, GameMode, Genre, Language, RequestType, SubType};
+use crate::error::{Result, SayobotError};
+
+const RANGE_MIN: f64 = 0.0;
+const RANGE_MAX: f64 = 9999.0;
+const RANGE_KEYS: &[&str] = &["star", "ar", "od", "cs", "hp", "bpm", "length"];
+const FLAG_KEYS: &[&str] = &["mode", "status", "genre", "lang", "in"];
+
+/// Why a query did not parse, and where.
+///
+/// `position` and `len` count characters, not bytes, so they can be used
+/// directly to underline the offending part of the input.
+#[derive(Debug, Clone, PartialEq, Eq, Error)]
+#[error("{message} at column {}", .position + 1)]
+pub struct QueryError {
+    pub position: usize,
+    pub len: usize,
+    pub message: String,
+}
+
+impl From<QueryError> for SayobotError {
+    fn from(err: QueryError) -> Self {
+        SayobotError::InvalidParams(err.to_string())
+    }
+}
+
+type Names = &'static [(&'static str, u32)];
+
+// the first name of every bit is the one written back
+const MODES: Names = &[
+    ("std", GameMode::STD.bits()),
+    ("taiko", GameMode::TAIKO.bits()),
+    ("ctb", GameMode::CTB.bits()),
+    ("mania", GameMode::MANIA.bits()),
+    ("osu", GameMode::STD.bits()),
+    ("catch", GameMode::CTB.bits()),
+    ("fruits", GameMode::CTB.bits()),
+];
+const STATUSES: Names = &[
+    ("ranked", Class::RANKED_APPROVED.bits()),
+    ("qualified", Class::QUALIFIED.bits()),
+    ("loved", Class::LOVED.bits()),
+    ("pending", Class::PENDING_WIP.bits()),
+    ("graveyard", Class::GRAVEYARD.bits()),
+    ("approved", Class::RANKED_APPROVED.bits()),
+    ("wip", Class::PENDING_WIP.bits()),
+];
+const GENRES: Names = &[
+    ("any", Genre::ANY.bits()),
+    ("unspecified", Genre::UNSPECIFIED.bits()),
+    ("videogame", Genre::VIDEO_GAME.bits()),
+    ("anime", Genre::ANIME.bits()),
+    ("rock", Genre::ROCK.bits()),
+    ("pop", Genre::POP.bits()),
+    ("other", Genre::OTHER.bits()),
+    ("novelty", Genre::NOVELTY.bits()),
+    ("hiphop", Genre::HIPHOP.bits()),
+    ("electronic", Genre::ELECTRONIC.bits()),
+    ("game", Genre::VIDEO_GAME.bits()),
+];
+const LANGUAGES: Names = &[
+    ("any", Language::ANY.bits()),
+    ("other", Language::OTHER.bits()),
+    ("english", Language::ENGLISH.bits()),
+    ("japanese", Language::JAPANESE.bits()),
+    ("chinese", Language::CHINESE.bits()),
+    ("instrumental", Language::INSTRUMENTAL.bits()),
+    ("korean", Language::KOREAN.bits()),
+    ("french", Language::FRENCH.bits()),
+    ("german", Language::GERMAN.bits()),
+    ("swedish", Language::SWEDISH.bits()),
+    ("spanish", Language::SPANISH.bits()),
+    ("italian", Language::ITALIAN.bits()),
+];
+const SUB_TYPES: Names = &[
+    ("title", SubType::TITLE.bits()),
+    ("artist", SubType::ARTIST.bits()),
+    ("creator", SubType::CREATOR.bits()),
+    ("version", SubType::VERSION.bits()),
+    ("tags", SubType::TAGS.bits()),
+    ("source", SubType::SOURCE.bits()),
+    ("mapper", SubType::CREATOR.bits()),
+    ("diff", SubType::VERSION.bits()),
+];
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Eq,
+    Ge,
+    Gt,
+    Le,
+    Lt,
+}
+
+/// A whitespace separated piece of the input; `start` is a byte offset.
+struct Token<'a> {
+    text: &'a str,
+    start: usize,
+    quoted: bool,
+}
+
+struct Parser<'a> {
+    input: &'a str,
+    params: SearchParams,
+    keyword: Vec<&'a str>,
+}
+
+impl<'a> Parser<'a> {
+    fn error(&self, start: usize, end: usize, message: impl Into<String>) -> QueryError {
+        let position = self.input[..start].chars().count();
+        QueryError {
+            position,
+            len: self.input[start..end].chars().count().max(1),
+            message: message.into(),
+        }
+    }
+
+    fn tokens(&self) -> Result<Vec<Token<'a>>, QueryError> {
+        let input = self.input;
+        let mut tokens = Vec::new();
+        let mut chars = input.char_indices().peekable();
+        while let Some(&(start, c)) = chars.peek() {
+            if c.is_whitespace() {
+                chars.next();
+            } else if c == '"' {
+                chars.next();
+                let end = loop {
+                    match chars.next() {
+                        Some((end, '"')) => break end,
+                        Some(_) => {}
+                        None => return Err(self.error(start, start + 1, "unclosed quote")),
+                    }
+                };
+                tokens.push(Token {
+                    text: &input[start + 1..end],
+                    start: start + 1,
+                    quoted: true,
+                });
+            } else {
+                let mut end = input.len();
+                while let Some(&(index, c)) = chars.peek() {
+                    if c.is_whitespace() || c == '"' {
+                        end = index;
+                        break;
+                    }
+                    chars.next();
+                }
+                tokens.push(Token {
+                    text: &input[start..end],
+                    start,
+                    quoted: false,
+                });
+            }
+        }
+        Ok(tokens)
+    }
+
+    fn term(&mut self, token: Token<'a>) -> Result<(), QueryError> {
+        let text = token.text;
+        let split = match text.find([':', '=', '<', '>']) {
+            Some(split) if !token.quoted => split,
+            _ => {
+                if !text.is_empty() {
+                    self.keyword.push(text);
+                }
+                return Ok(());
+            }
+        };
+        let key_end = token.start + split;
+        if split == 0 {
+            return Err(self.error(token.start, token.start + 1, "missing key before operator"));
+        }
+        if !text.starts_with(|c: char| c.is_ascii_alphabetic()) {
+            self.keyword.push(text);
+            return Ok(());
+        }
+        let rest = &text[split..];
+        let (op, op_len) = if rest.starts_with(">=") {
+            (Op::Ge, 2)
+        } else if rest.starts_with("<=") {
+            (Op::Le, 2)
+        } else if rest.starts_with('>') {
+            (Op::Gt, 1)
+        } else if rest.starts_with('<') {
+            (Op::Lt, 1)
+        } else {
+            (Op::Eq, 1)
+        };
+        let value_start = key_end + op_len;
+        let value = &text[split + op_len..];
+        if value.is_empty() {
+            return Err(self.error(key_end, value_start, "missing value"));
+        }
+        let key = text[..split].to_lowercase();
+        let names = match key.as_str() {
+            "mode" => Some((MODES, &mut self.params.mode)),
+            "status" | "class" => Some((STATUSES, &mut self.params.class)),
+            "genre" => Some((GENRES, &mut self.params.genre)),
+            "lang" | "language" => Some((LANGUAGES, &mut self.params.language)),
+            "in" | "sub" => Some((SUB_TYPES, &mut self.params.sub_type)),
+            _ => None,
+        };
+        if let Some((names, slot)) = names {
+            if op != Op::Eq {
+                return Err(self.error(key_end, value_start, format!("{} only takes ':'", key)));
+            }
+            let mut bits = slot.unwrap_or_default();
+            let mut offset = value_start;
+            for name in value.split(',') {
+                let found = names
+                    .iter()
+                    .find(|(known, _)| known.eq_ignore_ascii_case(name))
+                    .map(|(_, bit)| *bit);
+                match found {
+                    Some(bit) => bits |= bit,
+                    None => {
+                        let message = format!(
+                            "unknown {} {:?}, expected one of {}",
+                            key,
+                            name,
+                            canonical(names).join(", ")
+                        );
+                        return Err(self.error(offset, offset + name.len(), message));
+                    }
+                }
+                offset += name.len() + 1;
+            }
+            *slot = Some(bits);
+            return Ok(());
+        }
+        if !RANGE_KEYS.contains(&key.as_str()) {
+            let known: Vec<&str> = RANGE_KEYS.iter().chain(FLAG_KEYS).copied().collect();
+            let message = format!("unknown key {:?}, expected one of {}", key, known.join(", "));
+            return Err(self.error(token.start, key_end, message));
+        }
+        self.range(&key, op, value, value_start)
+    }
+
+    fn range(
+        &mut self,
+        key: &str,
+        op: Op,
+        value: &str,
+        value_start: usize,
+    ) -> Result<(), QueryError> {
+        let value_end = value_start + value.len();
+        let number = |text: &str, start: usize| -> Result<Option<f64>, QueryError> {
+            if text.is_empty() {
+                return Ok(None);
+            }
+            text.parse::<f64>()
+                .ok()
+                .filter(|number| number.is_finite())
+                .map(Some)
+                .ok_or_else(|| {
+                    self.error(
+                        start,
+                        start + text.len(),
+                        format!("{:?} is not a number", text),
+                    )
+                })
+        };
+        let (low, high) = match value.split_once('~') {
+            Some(_) if op != Op::Eq => {
+                return Err(self.error(value_start, value_end, "use ':' with a start~end range"))
+            }
+            Some((start, end)) => (
+                number(start, value_start)?,
+                number(end, value_start + start.len() + 1)?,
+            ),
+            None => {
+                let bound = number(value, value_start)?;
+                match op {
+                    Op::Eq => (bound, bound),
+                    Op::Ge | Op::Gt => (bound, None),
+                    Op::Le | Op::Lt => (None, bound),
+                }
+            }
+        };
+        if low.is_none() && high.is_none() {
+            return Err(self.error(value_start, value_end, "range needs a start or an end"));
+        }
+        let existing = self.params.filter.as_ref().and_then(|filter| {
+            filter
+                .ranges()
+                .find(|(name, _)| *name == key)
+                .map(|(_, range)| range)
+        });
+        let (start, end) = match (op, existing) {
+            // comparisons narrow what is already there
+            (Op::Ge | Op::Gt | Op::Le | Op::Lt, Some(existing)) => (
+                low.map_or(existing.start, |low| low.max(existing.start)),
+                high.map_or(existing.end, |high| high.min(existing.end)),
+            ),
+            _ => (low.unwrap_or(RANGE_MIN), high.unwrap_or(RANGE_MAX)),
+        };
+        let range = Range::new(start, end).map_err(|err| {
+            let reason = match err {
+                SayobotError::InvalidParams(reason) => reason,
+                other => other.to_string(),
+            };
+            self.error(value_start, value_end, reason)
+        })?;
+        self.params
+            .filter
+            .get_or_insert_with(AdvancedFilter::default)
+            .insert(key, range);
+        Ok(())
+    }
+}
+
+fn canonical(names: Names) -> Vec<&'static str> {
+    let mut seen = 0;
+    let mut canonical = Vec::new();
+    for (name, bit) in names {
+        if seen & bit == 0 {
+            seen |= bit;
+            canonical.push(*name);
+        }
+    }
+    canonical
+}
+
+fn flag_names(names: Names, bits: u32) -> Vec<&'static str> {
+    let mut seen = 0;
+    let mut found = Vec::new();
+    for (name, bit) in names {
+        if bits & bit != 0 && seen & bit == 0 {
+            seen |= bit;
+            found.push(*name);
+        }
+    }
+    found
+}
+
+impl SearchParams {
+    /// Parses a query such as `kano star:4~6 ar>=9 mode:std status:ranked,loved`.
+    ///
+    /// See the [`crate::query`] module for the syntax.
+    pub fn parse_query(query: &str) -> Result<Self, QueryError> {
+        let mut parser = Parser {
+            input: query,
+            params: SearchParams::default(),
+            keyword: Vec::new(),
+        };
+        for token in parser.tokens()? {
+            parser.term(token)?;
+        }
+        let mut params = parser.params;
+        if !parser.keyword.is_empty() {
+            params.keyword = Some(parser.keyword.join(" "));
+        }
+        if params.filter.as_ref().is_some_and(AdvancedFilter::is_empty) {
+            params.filter = None;
+        }
+        params.request_type = Some(RequestType::Search);
+        Ok(params)
+    }
+}
+
+impl FromStr for SearchParams {
+    type Err = QueryError;
+
+    fn from_str(query: &str) -> Result<Self, QueryError> {
+        SearchParams::parse_query(query)
+    }
+}
+
+/// Renders the search in the [`crate::query`] syntax. Limit, offset and the
+/// request type are not part of it.
+impl fmt::Display for SearchParams {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut terms = Vec::new();
+        if let Some(keyword) = self.keyword.as_deref().map(str::trim) {
+            if keyword.contains([':', '=', '<', '>']) {
+                terms.push(format!("\"{}\"", keyword.replace('"', "")));
+            } else if !keyword.is_empty() {
+                terms.push(keyword.replace('"', ""));
+            }
+        }
+        for (key, names, bits) in [
+            ("in", SUB_TYPES, self.sub_type),
+            ("mode", MODES, self.mode),
+            ("status", STATUSES, self.class),
+            ("genre", GENRES, self.genre),
+            ("lang", LANGUAGES, self.language),
+        ] {
+            let names = flag_names(names, bits.unwrap_or_default());
+            if !names.is_empty() {
+                terms.push(format!("{}:{}", key, names.join(",")));
+            }
+        }
+        if let Some(filter) = &self.filter {
+            for (key, range) in filter.ranges() {
+                terms.push(format!("{}:{}~{}", key, range.start, range.end));
+            }
+        }
+        f.write_str(&terms.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn range(start: f64, end: f64) -> Range {
+        Range::new(start, end).unwrap()
+    }
+
+    #[test]
+    fn parses_a_full_query() {
+        let params: SearchParams = "kano star:4~6 ar>=9 mode:std status:ranked,loved"
+            .parse()
+            .unwrap();
+        assert_eq!(params.keyword.as_deref(), Some("kano"));
+        assert_eq!(params.mode, Some(GameMode::STD.bits()));
+        assert_eq!(
+            params.class,
+            Some((Class::RANKED_APPROVED | Class::LOVED).bits())
+        );
+        let filter = params.filter.unwrap();
+        assert_eq!(filter.star, Some(range(4.0, 6.0)));
+        assert_eq!(filter.ar, Some(range(9.0, 9999.0)));
+        assert_eq!(params.request_type, Some(RequestType::Search));
+    }
+
+    #[test]
+    fn covers_every_field() {
+        let params = SearchParams::parse_query(
+            "\"dear sweet\" days in:title,mapper genre:anime lang:japanese,english \
+             mode:taiko,catch od:8 cs<=4 hp:~5 bpm:150~ length<=120 ar>=9 ar<=10",
+        )
+        .unwrap();
+        assert_eq!(params.keyword.as_deref(), Some("dear sweet days"));
+        assert_eq!(
+            params.sub_type,
+            Some((SubType::TITLE | SubType::CREATOR).bits())
+        );
+        assert_eq!(params.genre, Some(Genre::ANIME.bits()));
+        assert_eq!(
+            params.language,
+            Some((Language::JAPANESE | Language::ENGLISH).bits())
+        );
+        assert_eq!(params.mode, Some((GameMode::TAIKO | GameMode::CTB).bits()));
+        let filter = params.filter.unwrap();
+        assert_eq!(filter.od, Some(range(8.0, 8.0)));
+        assert_eq!(filter.cs, Some(range(0.0, 4.0)));
+        assert_eq!(filter.hp, Some(range(0.0, 5.0)));
+        assert_eq!(filter.bpm, Some(range(150.0, 9999.0)));
+        assert_eq!(filter.length, Some(range(0.0, 120.0)));
+        assert_eq!(filter.ar, Some(range(9.0, 10.0)));
+    }
+
+    #[test]
+    fn display_round_trips() {
+        let query = "kano in:title,artist mode:std,mania status:ranked,loved genre:anime \
+                     lang:japanese star:4~6.5 ar:9~9999 length:0~180";
+        let params = SearchParams::parse_query(query).unwrap();
+        assert_eq!(params.to_string(), query);
+        let again = SearchParams::parse_query(&params.to_string()).unwrap();
+        assert_eq!(again.to_string(), query);
+        assert_eq!(
+            SearchParams::parse_query("\"a:b\"").unwrap().to_string(),
+            "\"a:b\""
+        );
+        assert_eq!(SearchParams::default().to_string(), "");
+    }
+
+    #[test]
+    fn only_known_keys_reach_the_filter() {
+        let params = SearchParams::parse_query("kano 10:30 star:5").unwrap();
+        assert_eq!(params.keyword.as_deref(), Some("kano 10:30"));
+        let filter = params.filter.as_ref().unwrap();
+        assert_eq!(filter.star, Some(range(5.0, 5.0)));
+        assert_eq!(filter.ranges().count(), 1);
+        let printed = params.to_string();
+        assert_eq!(printed, "\"kano 10:30\" star:5~5");
+        let again = SearchParams::parse_query(&printed).unwrap();
+        assert_eq!(again.keyword.as_deref(), Some("kano 10:30"));
+    }
+
+    #[test]
+    fn errors_point_at_the_problem() {
+        let cases = [
+            ("kano mode:std,osz", 14, 3, "unknown mode"),
+            ("kano star:4~x", 12, 1, "not a number"),
+            ("ar:9 :3", 5, 1, "missing key"),
+            ("mode>=std", 4, 2, "only takes"),
+            ("star:", 4, 1, "missing value"),
+            ("カノ star:6~4", 8, 3, "less than"),
+            ("kano \"open", 5, 1, "unclosed quote"),
+            ("star>=4~6", 6, 3, "start~end"),
+            ("s-r:1~2", 0, 3, "unknown key"),
+            ("kano stars:5", 5, 5, "unknown key"),
+            ("kano max:1~2", 5, 3, "unknown key"),
+        ];
+        for (query, position, len, message) in cases {
+            let err = SearchParams::parse_query(query).unwrap_err();
+            assert_eq!(
+                (err.position, err.len),
+                (position, len),
+                "{}: {}",
+                query,
+                err
+            );
+            assert!(err.message.contains(message), "{}: {}", query, err);
+        }
+        let err = SearchParams::parse_query("mode:x").unwrap_err();
+        assert!(err.to_string().ends_with("at column 6"), "{}", err);
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use tokio::sync::{OwnedSemaphorePermit, Semaphore};
+
+use crate::error::{Result, SayobotError};
+
+/// Client-side throttling applied to every request the client makes.
+///
+/// `requests_per_second` feeds a token bucket holding up to `burst` tokens;
+/// `max_concurrent_per_host` caps in-flight requests (including download
+/// bodies still streaming) per host. Both are disabled by default.
+#[derive(Debug, Clone, Default)]
+pub struct RateLimit {
+    requests_per_second: Option<f64>,
+    burst: Option<u32>,
+    max_concurrent_per_host: Option<usize>,
+}
+
+impl RateLimit {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// `0` disables the rate; negative and non-finite rates fail the client build.
+    pub fn set_requests_per_second(mut self, requests_per_second: f64) -> Self {
+        self.requests_per_second = Some(requests_per_second);
+        self
+    }
+    /// Requests allowed back to back before the rate kicks in; defaults to 1.
+    pub fn set_burst(mut self, burst: u32) -> Self {
+        self.burst = Some(burst);
+        self
+    }
+    pub fn set_max_concurrent_per_host(mut self, max_concurrent: usize) -> Self {
+        self.max_concurrent_per_host = Some(max_concurrent);
+        self
+    }
+
+    pub(crate) fn validate(&self) -> Result<()> {
+        match self.requests_per_second {
+            Some(rate) if !rate.is_finite() || rate < 0.0 => {
+                Err(SayobotError::invalid_params(format!(
+                    "requests per second must be finite and not negative, got {}",
+                    rate
+                )))
+            }
+            _ => Ok(()),
+        }
+    }
+}
+
+pub(crate) struct Limiter {
+    bucket: Option<TokenBucket>,
+    hosts: Option<HostSemaphores>,
+}
+
+impl Limiter {
+    pub(crate) fn new(config: &RateLimit) -> Self {
+        let bucket = config
+            .requests_per_second
+            .filter(|rate| *rate > 0.0)
+            .map(|rate| TokenBucket::new(rate, config.burst.unwrap_or(1).max(1)));
+        let hosts = config
+            .max_concurrent_per_host
+            .filter(|max| *max > 0)
+            .map(HostSemaphores::new);
+        Self { bucket, hosts }
+    }
+
+    /// Waits for a token and a slot on `host`; the slot is held by the returned permit.
+    pub(crate) async fn acquire(&self, host: &str) -> Option<OwnedSemaphorePermit> {
+        let permit = match &self.hosts {
+            Some(hosts) => hosts.acquire(host).await,
+            None => None,
+        };
+        if let Some(bucket) = &self.bucket {
+            bucket.acquire().await;
+        }
+        permit
+    }
+}
+
+struct TokenBucket {
+    capacity: f64,
+    refill_per_sec: f64,
+    state: Mutex<BucketState>,
+}
+
+struct BucketState {
+    tokens: f64,
+    last_refill: Instant,
+}
+
+impl TokenBucket {
+    fn new(refill_per_sec: f64, burst: u32) -> Self {
+        let capacity = f64::from(burst);
+        Self {
+            capacity,
+            refill_per_sec,
+            state: Mutex::new(BucketState {
+                tokens: capacity,
+                last_refill: Instant::now(),
+            }),
+        }
+    }
+
+    async fn acquire(&self) {
+        loop {
+            match self.try_acquire() {
+                None => return,
+                Some(wait) => tokio::time::sleep(wait).await,
+            }
+        }
+    }
+
+    /// Takes a token, or returns how long until one is available.
+    fn try_acquire(&self) -> Option<Duration> {
+        let mut state = self.state.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let now = Instant::now();
+        let elapsed = now.duration_since(state.last_refill).as_secs_f64();
+        state.tokens = (state.tokens + elapsed * self.refill_per_sec).min(self.capacity);
+        state.last_refill = now;
+        if state.tokens >= 1.0 {
+            state.tokens -= 1.0;
+            None
+        } else {
+            // a tiny rate can ask for more than a Duration holds
+            let wait = (1.0 - state.tokens) / self.refill_per_sec;
+            Some(Duration::try_from_secs_f64(wait).unwrap_or(Duration::MAX))
+        }
+    }
+}
+
+struct HostSemaphores {
+    max_concurrent: usize,
+    semaphores: Mutex<HashMap<String, Arc<Semaphore>>>,
+}
+
+impl HostSemaphores {
+    fn new(max_concurrent: usize) -> Self {
+        Self {
+            max_concurrent,
+            semaphores: Mutex::new(HashMap::new()),
+        }
+    }
+
+    async fn acquire(&self, host: &str) -> Option<OwnedSemaphorePermit> {
+        let semaphore = {
+            let mut semaphores = self
+                .semaphores
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner());
+            semaphores
+                .entry(host.to_string())
+                .or_insert_with(|| Arc::new(Semaphore::new(self.max_concurrent)))
+                .clone()
+        };
+        // the semaphores are never closed, so this only yields `None` in theory
+        semaphore.acquire_owned().await.ok()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn bucket_allows_burst_then_waits() {
+        let bucket = TokenBucket::new(10.0, 2);
+        assert!(bucket.try_acquire().is_none());
+        assert!(bucket.try_acquire().is_none());
+        let wait = bucket.try_acquire().unwrap();
+        assert!(wait > Duration::ZERO && wait <= Duration::from_millis(100));
+    }
+
+    #[test]
+    fn bad_rates_are_rejected_and_tiny_ones_saturate() {
+        for rate in [f64::NAN, f64::INFINITY, -1.0] {
+            let config = RateLimit::new().set_requests_per_second(rate);
+            assert!(matches!(
+                config.validate(),
+                Err(SayobotError::InvalidParams(_))
+            ));
+        }
+        assert!(RateLimit::new()
+            .set_requests_per_second(0.0)
+            .validate()
+            .is_ok());
+
+        let bucket = TokenBucket::new(1e-300, 1);
+        assert!(bucket.try_acquire().is_none());
+        assert_eq!(bucket.try_acquire(), Some(Duration::MAX));
+    }
+
+    #[tokio::test]
+    async fn host_slots_are_released_with_permit() {
+        let limiter = Limiter::new(&RateLimit::new().set_max_concurrent_per_host(1));
+        let first = limiter.acquire("api.sayobot.cn").await;
+        // other hosts are not affected
+        let _other = limiter.acquire("dl.sayobot.cn").await;
+        let blocked = tokio::time::timeout(
+            Duration::from_millis(20),
+            limiter.acquire("api.sayobot.cn"),
+        )
+        .await;
+        assert!(blocked.is_err());
+        drop(first);
+        assert!(limiter.acquire("api.sayobot.cn").await.is_some());
+    }
+}
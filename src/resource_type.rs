@@ -1,4 +1,5 @@
-use anyhow::anyhow;
+use crate::endpoints::Endpoints;
+use crate::error::{Result, SayobotError};
 
 #[repr(i32)]
 #[derive(Debug, Clone, Copy)]
@@ -24,24 +25,30 @@ impl PartialEq for ResourceType {
 }
 
 impl ResourceType {
-    pub fn get_type_url_format(&self) -> anyhow::Result<String> {
+    pub fn get_type_url_format(&self) -> Result<String> {
+        self.url_format_for(&Endpoints::default())
+    }
+    pub fn url_format_for(&self, endpoints: &Endpoints) -> Result<String> {
         match self {
             ResourceType::PreviewImg => {
-                Ok("https://a.sayobot.cn/beatmaps/${sid}/covers/cover.webp".to_string())
+                Ok(endpoints.asset_url("beatmaps/${sid}/covers/cover.webp"))
             }
-            ResourceType::PreviewAudio => { Ok("https://a.sayobot.cn/preview/${sid}.mp3".to_string()) }
+            ResourceType::PreviewAudio => { Ok(endpoints.asset_url("preview/${sid}.mp3")) }
 
             ResourceType::FullSizeMap => {
-                Ok("https://dl.sayobot.cn/beatmaps/download/full/${sid}".to_string())
+                Ok(endpoints.download_url("beatmaps/download/full/${sid}"))
             }
             ResourceType::NoVideoMap => {
-                Ok("https://dl.sayobot.cn/beatmaps/download/novideo/${sid}".to_string())
+                Ok(endpoints.download_url("beatmaps/download/novideo/${sid}"))
             }
             ResourceType::MiniMap => {
-                Ok("https://dl.sayobot.cn/beatmaps/download/mini/${sid}".to_string())
+                Ok(endpoints.download_url("beatmaps/download/mini/${sid}"))
             }
             _ => {
-                Err(anyhow!("not supported url format for {:?}", self))
+                Err(SayobotError::invalid_params(format!(
+                    "not supported url format for {:?}",
+                    self
+                )))
             }
         }
     }
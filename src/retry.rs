@@ -0,0 +1,237 @@
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rand::Rng;
+
+use crate::error::SayobotError;
+
+/// How failed requests are retried.
+///
+/// Delays grow exponentially from `backoff_base` and never exceed
+/// `backoff_cap`, not even when the server's `Retry-After` asks for longer.
+/// With jitter enabled the delay is picked at random between half and the
+/// full computed value so that many clients do not retry in lockstep.
+#[derive(Debug, Clone)]
+pub struct RetryPolicy {
+    max_attempts: u32,
+    backoff_base: Duration,
+    backoff_cap: Duration,
+    jitter: bool,
+    retry_on_timeout: bool,
+    retry_on_transport: bool,
+    retryable_statuses: Vec<u16>,
+    honour_retry_after: bool,
+}
+
+impl Default for RetryPolicy {
+    fn default() -> Self {
+        Self {
+            max_attempts: 3,
+            backoff_base: Duration::from_millis(200),
+            backoff_cap: Duration::from_secs(5),
+            jitter: true,
+            retry_on_timeout: true,
+            retry_on_transport: true,
+            retryable_statuses: vec![429, 500, 502, 503, 504],
+            honour_retry_after: true,
+        }
+    }
+}
+
+impl RetryPolicy {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    /// A policy making exactly one attempt.
+    pub fn none() -> Self {
+        Self::default().set_max_attempts(1)
+    }
+    /// Total attempts including the first one; `0` is treated as `1`.
+    pub fn set_max_attempts(mut self, max_attempts: u32) -> Self {
+        self.max_attempts = max_attempts.max(1);
+        self
+    }
+    pub fn set_backoff(mut self, base: Duration, cap: Duration) -> Self {
+        self.backoff_base = base;
+        self.backoff_cap = cap.max(base);
+        self
+    }
+    pub fn set_jitter(mut self, jitter: bool) -> Self {
+        self.jitter = jitter;
+        self
+    }
+    pub fn set_retry_on_timeout(mut self, retry: bool) -> Self {
+        self.retry_on_timeout = retry;
+        self
+    }
+    pub fn set_retry_on_transport(mut self, retry: bool) -> Self {
+        self.retry_on_transport = retry;
+        self
+    }
+    /// HTTP statuses worth another attempt.
+    pub fn set_retryable_statuses(mut self, statuses: Vec<u16>) -> Self {
+        self.retryable_statuses = statuses;
+        self
+    }
+    /// Waits for the server's `Retry-After` instead of the computed backoff,
+    /// up to the backoff cap.
+    pub fn set_honour_retry_after(mut self, honour: bool) -> Self {
+        self.honour_retry_after = honour;
+        self
+    }
+
+    pub fn max_attempts(&self) -> u32 {
+        self.max_attempts
+    }
+
+    pub fn is_retryable(&self, err: &SayobotError) -> bool {
+        match err {
+            SayobotError::Timeout(_) => self.retry_on_timeout,
+            SayobotError::Transport(_) => self.retry_on_transport,
+            SayobotError::HttpStatus { status } => self.retryable_statuses.contains(status),
+            _ => false,
+        }
+    }
+
+    /// Delay to wait after the given failed attempt (1-based).
+    pub fn backoff(&self, attempt: u32) -> Duration {
+        let exponent = attempt.saturating_sub(1).min(31);
+        let delay = self
+            .backoff_base
+            .saturating_mul(1u32 << exponent)
+            .min(self.backoff_cap);
+        if self.jitter && !delay.is_zero() {
+            let half = delay / 2;
+            half + rand::thread_rng().gen_range(Duration::ZERO..=half)
+        } else {
+            delay
+        }
+    }
+
+    /// Delay before the next attempt, preferring the server's `Retry-After`
+    /// hint; the hint is clamped to `backoff_cap`.
+    pub(crate) fn delay_for(&self, attempt: u32, retry_after: Option<Duration>) -> Duration {
+        match retry_after {
+            Some(hint) if self.honour_retry_after => hint.min(self.backoff_cap),
+            _ => self.backoff(attempt),
+        }
+    }
+}
+
+/// Parses a `Retry-After` value, either delay-seconds or an HTTP-date.
+///
+/// Only the IMF-fixdate form of HTTP-date (`Sun, 06 Nov 1994 08:49:37 GMT`) is
+/// understood; the obsolete RFC 850 and asctime forms are ignored. A date in
+/// the past means no delay.
+pub(crate) fn parse_retry_after(value: &str) -> Option<Duration> {
+    parse_retry_after_at(value, SystemTime::now())
+}
+
+fn parse_retry_after_at(value: &str, now: SystemTime) -> Option<Duration> {
+    let value = value.trim();
+    if let Ok(seconds) = value.parse::<u64>() {
+        return Some(Duration::from_secs(seconds));
+    }
+    let date = UNIX_EPOCH + Duration::from_secs(parse_imf_fixdate(value)?);
+    Some(date.duration_since(now).unwrap_or_default())
+}
+
+/// Seconds since the unix epoch of an IMF-fixdate.
+fn parse_imf_fixdate(value: &str) -> Option<u64> {
+    const MONTHS: [&str; 12] = [
+        "Jan", "Feb", "Mar", "Apr", "May", "Jun", "Jul", "Aug", "Sep", "Oct", "Nov", "Dec",
+    ];
+    let (_weekday, rest) = value.split_once(", ")?;
+    let parts: Vec<&str> = rest.split(' ').collect();
+    let [day, month, year, time, "GMT"] = parts.as_slice() else {
+        return None;
+    };
+    let day: u64 = day.parse().ok().filter(|day| (1..=31).contains(day))?;
+    let month = MONTHS.iter().position(|name| name == month)? as u64 + 1;
+    let year: u64 = year.parse().ok().filter(|year| *year >= 1970)?;
+    let mut clock = time.split(':').map(|part| part.parse::<u64>().ok());
+    let (Some(Some(hour)), Some(Some(minute)), Some(Some(second)), None) =
+        (clock.next(), clock.next(), clock.next(), clock.next())
+    else {
+        return None;
+    };
+    if hour > 23 || minute > 59 || second > 60 {
+        return None;
+    }
+    // days from civil, shifted so the year starts in March
+    let (y, m) = if month <= 2 {
+        (year - 1, month + 9)
+    } else {
+        (year, month - 3)
+    };
+    let era = y / 400;
+    let year_of_era = y % 400;
+    let day_of_year = (153 * m + 2) / 5 + day - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    let days = era * 146_097 + day_of_era - 719_468;
+    Some(days * 86_400 + hour * 3_600 + minute * 60 + second)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn backoff_grows_and_is_capped() {
+        let policy = RetryPolicy::new()
+            .set_jitter(false)
+            .set_backoff(Duration::from_millis(100), Duration::from_millis(500));
+        assert_eq!(policy.backoff(1), Duration::from_millis(100));
+        assert_eq!(policy.backoff(2), Duration::from_millis(200));
+        assert_eq!(policy.backoff(3), Duration::from_millis(400));
+        assert_eq!(policy.backoff(4), Duration::from_millis(500));
+        assert_eq!(policy.backoff(40), Duration::from_millis(500));
+    }
+
+    #[test]
+    fn jitter_stays_within_bounds() {
+        let policy = RetryPolicy::new()
+            .set_backoff(Duration::from_millis(100), Duration::from_secs(1));
+        for _ in 0..100 {
+            let delay = policy.backoff(2);
+            assert!(delay >= Duration::from_millis(100) && delay <= Duration::from_millis(200));
+        }
+    }
+
+    #[test]
+    fn retry_after_wins_when_honoured() {
+        let policy = RetryPolicy::new().set_jitter(false);
+        let hint = parse_retry_after(" 3 ");
+        assert_eq!(policy.delay_for(1, hint), Duration::from_secs(3));
+        let policy = policy.set_honour_retry_after(false);
+        assert_eq!(policy.delay_for(1, hint), Duration::from_millis(200));
+    }
+
+    #[test]
+    fn retry_after_is_capped_and_accepts_dates() {
+        let policy = RetryPolicy::new().set_jitter(false);
+        let day = parse_retry_after("86400");
+        assert_eq!(policy.delay_for(1, day), Duration::from_secs(5));
+
+        let now = UNIX_EPOCH + Duration::from_secs(784_111_767);
+        assert_eq!(
+            parse_retry_after_at("Sun, 06 Nov 1994 08:49:37 GMT", now),
+            Some(Duration::from_secs(10))
+        );
+        assert_eq!(
+            parse_retry_after_at("Sun, 06 Nov 1994 08:49:00 GMT", now),
+            Some(Duration::ZERO)
+        );
+        assert_eq!(parse_imf_fixdate("Tue, 29 Feb 2000 00:00:00 GMT"), Some(951_782_400));
+        assert_eq!(parse_retry_after_at("Sunday, 06-Nov-94 08:49:37 GMT", now), None);
+        assert_eq!(parse_retry_after_at("soon", now), None);
+    }
+
+    #[test]
+    fn only_transient_errors_are_retryable() {
+        let policy = RetryPolicy::new();
+        assert!(policy.is_retryable(&SayobotError::HttpStatus { status: 503 }));
+        assert!(!policy.is_retryable(&SayobotError::HttpStatus { status: 404 }));
+        assert!(!policy.is_retryable(&SayobotError::NotFound("1".to_string())));
+        assert_eq!(RetryPolicy::none().max_attempts(), 1);
+    }
+}
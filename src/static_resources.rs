@@ -1,12 +1,17 @@
 use std::path::{Path, PathBuf};
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
-use anyhow::{anyhow, Context, Result};
 use futures::stream::StreamExt;
 use tokio::fs::File;
 use tokio::io::AsyncWriteExt;
+use tracing::Instrument;
 
+use crate::beatmap_info_v2::{BeatmapKey, BeatmapLookup};
+use crate::client::SayobotClient;
+use crate::endpoints::Endpoints;
+use crate::error::{Result, SayobotError};
 use crate::resource_type::ResourceType;
+use crate::telemetry::{self, Endpoint};
 
 struct QueryParams {
     sid: Option<i64>,
@@ -15,28 +20,34 @@ struct QueryParams {
 }
 
 pub struct RequestBuilder {
+    client: SayobotClient,
     params: QueryParams,
     request_timeout: Duration,
 }
 
 impl Default for RequestBuilder {
     fn default() -> Self {
+        Self::new()
+    }
+}
+
+
+impl RequestBuilder {
+    /// Builder bound to [`SayobotClient::global`].
+    pub fn new() -> Self {
+        Self::with_client(SayobotClient::global().clone())
+    }
+    pub(crate) fn with_client(client: SayobotClient) -> Self {
         Self {
+            request_timeout: client.download_timeout(),
+            client,
             params: QueryParams {
                 sid: None,
                 resource_type: None,
                 download_path: Some(PathBuf::from(".")),
             },
-            request_timeout: Duration::from_secs(30),
         }
     }
-}
-
-
-impl RequestBuilder {
-    pub fn new() -> Self {
-        Self::default()
-    }
     pub fn set_sid(mut self, sid: i64) -> Self {
         self.params.sid = Some(sid);
         self
@@ -54,103 +65,194 @@ impl RequestBuilder {
             self.params.download_path = Some(path.as_ref().to_path_buf());
             Ok(self)
         } else {
-            Err(anyhow::anyhow!("path not exist"))
+            Err(SayobotError::invalid_params("path not exist"))
         }
     }
 
     pub async fn do_request(self) -> Result<String> {
-        let url = self.get_url().await?;
-        let client = reqwest::Client::builder()
-            .timeout(self.request_timeout)
-            .build()?;
-
-        let response = client.get(&url).send().await.expect("reqwest fail");
-
-        if response.status().is_success() {
-            let content_disposition = response.headers()
-                .get("Content-Disposition")
-                .and_then(|value| value.to_str().ok())
-                .and_then(|content| content.split("filename*=utf-8''").nth(1));
-            let file_name = match content_disposition {
-                Some(name) => urlencoding::decode(name)?.to_string(),
-                None => return Err(anyhow!("can't get file name from response header")),
-            };
-
-            let file_path = Path::new(self.params.download_path.as_ref().unwrap())
-                .join(file_name.clone());
-            let mut file = File::create(&file_path).await.expect("file create fail");
-
-            let mut stream = response.bytes_stream();
-            while let Some(chunk_result) = stream.next().await {
-                let chunk = chunk_result.expect("read chunk fail");
-                file.write_all(&chunk).await.expect("write to file fail");
-            }
-            Ok(file_name)
-        } else {
-            Err(anyhow!("http status not success {}", response.status()))
+        let span = telemetry::request_span(Endpoint::Download);
+        if let Some(sid) = self.params.sid {
+            span.record("sid", sid);
         }
+        let started = Instant::now();
+        let result = self.fetch().instrument(span.clone()).await;
+        telemetry::finish(&span, Endpoint::Download, started, &result);
+        result
     }
 
-    async fn get_url(&self) -> Result<String> {
-        let sid = self.params.sid.with_context(|| "sid not set")?;
-        let res_type = self.params.resource_type.with_context(|| "res_type not set")?;
+    async fn fetch(self) -> Result<String> {
+        let url = self.get_url().await?;
+        let response = self.client.get(&url, self.request_timeout).await?;
+        let file_name = file_name_from_headers(response.headers())?;
 
-        if let ResourceType::PreviewImg | ResourceType::PreviewAudio | ResourceType::FullSizeMap | ResourceType::NoVideoMap | ResourceType::MiniMap = res_type {
-            let url_format = res_type.get_type_url_format()?;
-            return Ok(url_format.replace("${sid}", &sid.to_string()));
-        }
+        let file_path = self
+            .params
+            .download_path
+            .as_deref()
+            .unwrap_or_else(|| Path::new("."))
+            .join(&file_name);
+        let mut file = File::create(&file_path).await?;
 
-        let v2_map_info = crate::beatmap_info_v2::RequestBuilder::new()
-            .set_key("sid".to_string())
-            .set_timeout(self.request_timeout)
-            .do_request()
-            .await
-            .expect("get beatmap info failed");
+        let mut written = 0u64;
+        let mut stream = response.bytes_stream();
+        while let Some(chunk_result) = stream.next().await {
+            let chunk = chunk_result?;
+            file.write_all(&chunk).await?;
+            written += chunk.len() as u64;
+        }
+        file.flush().await?;
+        telemetry::record_download_bytes(written);
+        Ok(file_name)
+    }
 
-        let matched_map = v2_map_info.data.bid_data
-            .iter()
-            .find(|map| map.bid == sid)
-            .expect("map not found");
+    async fn get_url(&self) -> Result<String> {
+        let sid = self
+            .params
+            .sid
+            .ok_or_else(|| SayobotError::invalid_params("sid not set"))?;
+        let res_type = self
+            .params
+            .resource_type
+            .ok_or_else(|| SayobotError::invalid_params("res_type not set"))?;
 
         match res_type {
-            ResourceType::FullAudio => {
-                let audio_file_name = matched_map.audio.clone();
-                build_file_url(sid, audio_file_name)
+            ResourceType::PreviewImg
+            | ResourceType::PreviewAudio
+            | ResourceType::FullSizeMap
+            | ResourceType::NoVideoMap
+            | ResourceType::MiniMap => {
+                let url_format = res_type.url_format_for(self.client.endpoints())?;
+                Ok(url_format.replace("${sid}", &sid.to_string()))
             }
-            ResourceType::FullCoverImg => {
-                let cover_file_name = matched_map.bg.clone();
-                build_file_url(sid, cover_file_name)
-            }
-            ResourceType::Video => Err(anyhow!("video not supported")),
+            ResourceType::FullAudio | ResourceType::FullCoverImg => {
+                let key = BeatmapKey::Sid(sid);
+                let v2_map_info = self
+                    .client
+                    .beatmap_info()
+                    .set_beatmap_key(key.clone())
+                    .set_timeout(self.request_timeout)
+                    .do_request()
+                    .await?;
+                let lookup = BeatmapLookup::new(v2_map_info.data, &key);
 
-            _ => unreachable!(),
+                let matched_map = lookup
+                    .difficulty()
+                    .ok_or_else(|| SayobotError::NotFound(format!("no beatmap in set {}", sid)))?;
+                let file_name = if res_type == ResourceType::FullAudio {
+                    matched_map.audio.clone()
+                } else {
+                    matched_map.bg.clone()
+                };
+                build_file_url(self.client.endpoints(), sid, file_name)
+            }
+            ResourceType::Video => Err(SayobotError::invalid_params("video not supported")),
         }
     }
 }
 
-fn build_file_url(sid: i64, file_name: String) -> Result<String> {
-    Ok(format!(
-        "https://dl.sayobot.cn/beatmaps/files/{}/{}",
-        sid, file_name
-    ))
+/// Extracts the file name from `Content-Disposition: ...; filename*=utf-8''<name>`.
+fn file_name_from_headers(headers: &http::header::HeaderMap) -> Result<String> {
+    let encoded = headers
+        .get(http::header::CONTENT_DISPOSITION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|content| content.split("filename*=utf-8''").nth(1))
+        .ok_or_else(|| {
+            SayobotError::decode("can't get file name from response header", "")
+        })?;
+    let name = urlencoding::decode(encoded)
+        .map_err(|err| SayobotError::decode(err, encoded))?;
+    // never let the server pick a path outside the download directory
+    match Path::new(name.as_ref()).file_name() {
+        Some(file_name) => Ok(file_name.to_string_lossy().into_owned()),
+        None => Err(SayobotError::decode("invalid file name in response header", encoded)),
+    }
+}
+
+fn build_file_url(endpoints: &Endpoints, sid: i64, file_name: String) -> Result<String> {
+    Ok(endpoints.download_url(&format!("beatmaps/files/{}/{}", sid, file_name)))
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::mock_server::{MockServer, FAKE_MP3, FAKE_OSZ};
+
+    fn temp_dir(name: &str) -> PathBuf {
+        let dir = std::env::temp_dir().join(format!("sayobot-{}-{}", name, std::process::id()));
+        std::fs::create_dir_all(&dir).unwrap();
+        dir
+    }
 
     #[tokio::test]
     async fn test_download_mini_map() {
-        let builder = RequestBuilder::new()
-        .set_sid(2045169
-        ).set_resource_type(ResourceType::MiniMap)
-        .set_download_path("./").unwrap()
-        .set_timeout(Duration::from_secs(100));
-        let result = builder.do_request().await;
-        assert!(result.is_ok());
-        let file_name = result.unwrap();
-        if Path::new(&file_name).exists() {
-            std::fs::remove_file(&file_name).unwrap();
-        }
+        let server = MockServer::start().await.unwrap();
+        let dir = temp_dir("mini-map");
+        let builder = server
+            .client()
+            .unwrap()
+            .download()
+            .set_sid(2045169)
+            .set_resource_type(ResourceType::MiniMap)
+            .set_download_path(&dir)
+            .unwrap()
+            .set_timeout(Duration::from_secs(100));
+        let file_name = builder.do_request().await.unwrap();
+        assert_eq!(file_name, "2045169 kano - Dear Sweet Days [mini].osz");
+        assert_eq!(std::fs::read(dir.join(&file_name)).unwrap(), FAKE_OSZ);
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_download_full_audio() {
+        let server = MockServer::start().await.unwrap();
+        let dir = temp_dir("full-audio");
+        let file_name = server
+            .client()
+            .unwrap()
+            .download()
+            .set_sid(2035712)
+            .set_resource_type(ResourceType::FullAudio)
+            .set_download_path(&dir)
+            .unwrap()
+            .do_request()
+            .await
+            .unwrap();
+        assert_eq!(file_name, "audio.mp3");
+        assert_eq!(std::fs::read(dir.join(&file_name)).unwrap(), FAKE_MP3);
+        assert_eq!(
+            server.requests(),
+            vec![
+                "/v2/beatmapinfo?0=2035712&1=1".to_string(),
+                "/beatmaps/files/2035712/audio.mp3".to_string()
+            ]
+        );
+        std::fs::remove_dir_all(&dir).unwrap();
+    }
+
+    #[test]
+    fn test_file_name_from_headers() {
+        let mut headers = http::header::HeaderMap::new();
+        headers.insert(
+            http::header::CONTENT_DISPOSITION,
+            "attachment; filename*=utf-8''2045169%20kano%20-%20Stella.osz"
+                .parse()
+                .unwrap(),
+        );
+        assert_eq!(
+            file_name_from_headers(&headers).unwrap(),
+            "2045169 kano - Stella.osz"
+        );
+
+        headers.insert(
+            http::header::CONTENT_DISPOSITION,
+            "attachment; filename*=utf-8''..%2F..%2Fevil.osz".parse().unwrap(),
+        );
+        assert_eq!(file_name_from_headers(&headers).unwrap(), "evil.osz");
+
+        headers.remove(http::header::CONTENT_DISPOSITION);
+        assert!(matches!(
+            file_name_from_headers(&headers),
+            Err(SayobotError::Decode { .. })
+        ));
     }
 }
@@ -0,0 +1,320 @@
+use std::path::Path;
+use std::sync::{Mutex, MutexGuard};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+use rusqlite::{params, Connection, OptionalExtension};
+
+use crate::beatmap_info_v2::{BuildInfo, ResponseData};
+use crate::error::{Result, SayobotError};
+
+/// SQLite catalogue of every beatmapset fetched through a client.
+///
+/// Sets are indexed by sid, creator and title, difficulties by bid. Each row
+/// remembers when it was fetched so stale entries can be refreshed selectively.
+pub struct MetadataStore {
+    conn: Mutex<Connection>,
+    max_age: Option<Duration>,
+}
+
+/// A beatmapset as persisted, with the unix time it was fetched.
+#[derive(Debug, Clone)]
+pub struct StoredSet {
+    pub data: ResponseData,
+    pub fetched_at: i64,
+}
+
+const SCHEMA: &str = "
+CREATE TABLE IF NOT EXISTS beatmapsets (
+    sid          INTEGER PRIMARY KEY,
+    creator      TEXT    NOT NULL,
+    title        TEXT    NOT NULL,
+    title_u      TEXT    NOT NULL,
+    last_update  INTEGER NOT NULL,
+    local_update INTEGER NOT NULL,
+    fetched_at   INTEGER NOT NULL,
+    data         TEXT    NOT NULL
+);
+CREATE INDEX IF NOT EXISTS beatmapsets_creator ON beatmapsets (creator COLLATE NOCASE);
+CREATE INDEX IF NOT EXISTS beatmapsets_title ON beatmapsets (title COLLATE NOCASE);
+CREATE TABLE IF NOT EXISTS beatmaps (
+    bid     INTEGER PRIMARY KEY,
+    sid     INTEGER NOT NULL REFERENCES beatmapsets (sid) ON DELETE CASCADE,
+    version TEXT    NOT NULL,
+    mode    INTEGER NOT NULL,
+    star    REAL    NOT NULL,
+    data    TEXT    NOT NULL
+);
+CREATE INDEX IF NOT EXISTS beatmaps_sid ON beatmaps (sid);
+";
+
+impl MetadataStore {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self> {
+        Self::init(Connection::open(path)?)
+    }
+    pub fn open_in_memory() -> Result<Self> {
+        Self::init(Connection::open_in_memory()?)
+    }
+    fn init(conn: Connection) -> Result<Self> {
+        conn.execute_batch("PRAGMA foreign_keys = ON;")?;
+        conn.execute_batch(SCHEMA)?;
+        Ok(Self {
+            conn: Mutex::new(conn),
+            max_age: None,
+        })
+    }
+    /// Lets the client answer beatmap info lookups from rows fetched within `max_age`.
+    pub fn set_max_age(mut self, max_age: Duration) -> Self {
+        self.max_age = Some(max_age);
+        self
+    }
+
+    /// Inserts or replaces a beatmapset and all of its difficulties.
+    pub fn upsert_set(&self, set: &ResponseData) -> Result<()> {
+        let mut conn = self.conn();
+        let tx = conn.transaction()?;
+        tx.execute(
+            "INSERT OR REPLACE INTO beatmapsets
+                (sid, creator, title, title_u, last_update, local_update, fetched_at, data)
+             VALUES (?1, ?2, ?3, ?4, ?5, ?6, ?7, ?8)",
+            params![
+                set.sid,
+                set.creator,
+                set.title,
+                set.title_u,
+                set.last_update,
+                set.local_update,
+                unix_now(),
+                to_json(set)?,
+            ],
+        )?;
+        tx.execute("DELETE FROM beatmaps WHERE sid = ?1", params![set.sid])?;
+        for map in &set.bid_data {
+            tx.execute(
+                "INSERT OR REPLACE INTO beatmaps (bid, sid, version, mode, star, data)
+                 VALUES (?1, ?2, ?3, ?4, ?5, ?6)",
+                params![map.bid, set.sid, map.version, map.mode, map.star, to_json(map)?],
+            )?;
+        }
+        tx.commit()?;
+        Ok(())
+    }
+
+    pub fn get_set(&self, sid: i64) -> Result<Option<StoredSet>> {
+        self.conn()
+            .query_row(
+                "SELECT data, fetched_at FROM beatmapsets WHERE sid = ?1",
+                params![sid],
+                |row| Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?)),
+            )
+            .optional()?
+            .map(|(data, fetched_at)| stored_set(&data, fetched_at))
+            .transpose()
+    }
+    /// The set containing difficulty `bid`.
+    pub fn get_set_by_bid(&self, bid: i64) -> Result<Option<StoredSet>> {
+        let sid: Option<i64> = self
+            .conn()
+            .query_row("SELECT sid FROM beatmaps WHERE bid = ?1", params![bid], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        match sid {
+            Some(sid) => self.get_set(sid),
+            None => Ok(None),
+        }
+    }
+    pub fn get_beatmap(&self, bid: i64) -> Result<Option<BuildInfo>> {
+        let data: Option<String> = self
+            .conn()
+            .query_row("SELECT data FROM beatmaps WHERE bid = ?1", params![bid], |row| {
+                row.get(0)
+            })
+            .optional()?;
+        data.map(|data| from_json(&data)).transpose()
+    }
+    /// Sets mapped by `creator`, compared case-insensitively.
+    pub fn find_by_creator(&self, creator: &str) -> Result<Vec<StoredSet>> {
+        self.query_sets(
+            "SELECT data, fetched_at FROM beatmapsets
+             WHERE creator = ?1 COLLATE NOCASE ORDER BY sid",
+            creator,
+        )
+    }
+    /// Sets whose romanised or unicode title contains `needle`.
+    pub fn find_by_title(&self, needle: &str) -> Result<Vec<StoredSet>> {
+        let pattern = format!(
+            "%{}%",
+            needle.replace('\\', "\\\\").replace('%', "\\%").replace('_', "\\_")
+        );
+        self.query_sets(
+            "SELECT data, fetched_at FROM beatmapsets
+             WHERE title LIKE ?1 ESCAPE '\\' OR title_u LIKE ?1 ESCAPE '\\' ORDER BY sid",
+            &pattern,
+        )
+    }
+    /// Sids fetched longer than `older_than` ago.
+    pub fn stale_sids(&self, older_than: Duration) -> Result<Vec<i64>> {
+        let cutoff = unix_now() - older_than.as_secs() as i64;
+        self.query_sids("SELECT sid FROM beatmapsets WHERE fetched_at < ?1 ORDER BY sid", cutoff)
+    }
+    /// Sids whose mirror copy (`local_update`) lags behind the osu! `last_update`.
+    pub fn outdated_sids(&self) -> Result<Vec<i64>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(
+            "SELECT sid FROM beatmapsets WHERE local_update < last_update ORDER BY sid",
+        )?;
+        let sids = stmt.query_map([], |row| row.get(0))?;
+        Ok(sids.collect::<rusqlite::Result<Vec<i64>>>()?)
+    }
+
+    /// Stored set for a lookup key if it is still fresh: by sid or bid as
+    /// `match_mode` says, else sid first, then bid.
+    #[cfg(any(test, feature = "client"))]
+    pub(crate) fn fresh_set(
+        &self,
+        key: &str,
+        match_mode: Option<i32>,
+    ) -> Result<Option<ResponseData>> {
+        let (Some(max_age), Ok(id)) = (self.max_age, key.trim().parse::<i64>()) else {
+            return Ok(None);
+        };
+        let stored = match match_mode {
+            None => match self.get_set(id)? {
+                Some(stored) => Some(stored),
+                None => self.get_set_by_bid(id)?,
+            },
+            Some(1) => self.get_set(id)?,
+            Some(2) => self.get_set_by_bid(id)?,
+            Some(_) => None,
+        };
+        let cutoff = unix_now() - max_age.as_secs() as i64;
+        Ok(stored
+            .filter(|stored| stored.fetched_at >= cutoff)
+            .map(|stored| stored.data))
+    }
+
+    fn query_sets(&self, sql: &str, param: &str) -> Result<Vec<StoredSet>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(sql)?;
+        let rows = stmt.query_map(params![param], |row| {
+            Ok((row.get::<_, String>(0)?, row.get::<_, i64>(1)?))
+        })?;
+        let mut sets = Vec::new();
+        for row in rows {
+            let (data, fetched_at) = row?;
+            sets.push(stored_set(&data, fetched_at)?);
+        }
+        Ok(sets)
+    }
+    fn query_sids(&self, sql: &str, param: i64) -> Result<Vec<i64>> {
+        let conn = self.conn();
+        let mut stmt = conn.prepare(sql)?;
+        let sids = stmt.query_map(params![param], |row| row.get(0))?;
+        Ok(sids.collect::<rusqlite::Result<Vec<i64>>>()?)
+    }
+    fn conn(&self) -> MutexGuard<'_, Connection> {
+        self.conn.lock().unwrap_or_else(|poisoned| poisoned.into_inner())
+    }
+}
+
+fn stored_set(data: &str, fetched_at: i64) -> Result<StoredSet> {
+    Ok(StoredSet {
+        data: from_json(data)?,
+        fetched_at,
+    })
+}
+
+fn to_json<T: serde::Serialize>(value: &T) -> Result<String> {
+    serde_json::to_string(value).map_err(|err| SayobotError::decode(err, ""))
+}
+
+fn from_json<T: serde::de::DeserializeOwned>(data: &str) -> Result<T> {
+    crate::error::decode_json(data)
+}
+
+fn unix_now() -> i64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|elapsed| elapsed.as_secs() as i64)
+        .unwrap_or_default()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sample_set(sid: i64, bid: i64, creator: &str, title: &str) -> ResponseData {
+        let map = serde_json::json!({
+            "AR": 9.0, "CS": 4.0, "HP": 6.0, "OD": 8.0, "aim": 2.5, "audio": "audio.mp3",
+            "bg": "bg.jpg", "bid": bid, "circles": 300, "hit300window": 32, "img": "",
+            "length": 120, "maxcombo": 700, "mode": 0, "passcount": 10, "playcount": 100,
+            "pp": 200.0, "pp_acc": 50.0, "pp_aim": 80.0, "pp_speed": 70.0, "sliders": 200,
+            "speed": 2.4, "spinners": 1, "star": 5.2, "strain_aim": "", "strain_speed": "",
+            "version": "Insane"
+        });
+        serde_json::from_value(serde_json::json!({
+            "approved": 1, "approved_date": 1600000000, "artist": "kano", "artistU": "鹿乃",
+            "bid_data": [map], "bids_amount": 1, "bpm": 180.0, "creator": creator,
+            "creator_id": 1, "favourite_count": 3, "genre": 3, "language": 3,
+            "last_update": 1600000000, "local_update": 1500000000, "preview": 1, "sid": sid,
+            "source": "", "storyboard": 0, "tags": "", "title": title, "titleU": title,
+            "video": 0
+        }))
+        .unwrap()
+    }
+
+    #[test]
+    fn upsert_and_query() {
+        let store = MetadataStore::open_in_memory().unwrap();
+        store.upsert_set(&sample_set(1, 11, "Sotarks", "Stella")).unwrap();
+        store.upsert_set(&sample_set(2, 22, "sotarks", "Other 100%")).unwrap();
+        // replacing a set keeps a single row
+        store.upsert_set(&sample_set(1, 11, "Sotarks", "Stella")).unwrap();
+
+        assert_eq!(store.get_set(1).unwrap().unwrap().data.title, "Stella");
+        assert_eq!(store.get_set_by_bid(22).unwrap().unwrap().data.sid, 2);
+        assert_eq!(store.get_beatmap(11).unwrap().unwrap().version, "Insane");
+        assert_eq!(store.find_by_creator("SOTARKS").unwrap().len(), 2);
+        assert_eq!(store.find_by_title("tel").unwrap().len(), 1);
+        assert_eq!(store.find_by_title("100%").unwrap()[0].data.sid, 2);
+        assert_eq!(store.outdated_sids().unwrap(), vec![1, 2]);
+        assert!(store.stale_sids(Duration::from_secs(60)).unwrap().is_empty());
+    }
+
+    #[test]
+    fn fresh_set_honours_max_age() {
+        let store = MetadataStore::open_in_memory().unwrap();
+        store.upsert_set(&sample_set(1, 11, "a", "b")).unwrap();
+        assert!(store.fresh_set("1", None).unwrap().is_none());
+
+        let store = store.set_max_age(Duration::from_secs(60));
+        assert_eq!(store.fresh_set("1", None).unwrap().unwrap().sid, 1);
+        assert_eq!(store.fresh_set("11", None).unwrap().unwrap().sid, 1);
+        assert_eq!(store.fresh_set("11", Some(2)).unwrap().unwrap().sid, 1);
+        assert!(store.fresh_set("11", Some(1)).unwrap().is_none());
+        assert!(store.fresh_set("kano", None).unwrap().is_none());
+    }
+
+    #[cfg(feature = "client")]
+    #[tokio::test]
+    async fn client_reads_and_fills_the_store() {
+        use crate::client::SayobotClient;
+        use crate::mock_server::MockServer;
+
+        let server = MockServer::start().await.unwrap();
+        let store = MetadataStore::open_in_memory()
+            .unwrap()
+            .set_max_age(Duration::from_secs(60));
+        let client = SayobotClient::builder()
+            .set_endpoints(server.endpoints())
+            .set_store(store)
+            .build()
+            .unwrap();
+        let fetched = client.lookup_set(2035712).await.unwrap();
+        let written = client.store().unwrap().get_beatmap(4231121).unwrap();
+        assert_eq!(written.unwrap().version, fetched.set.bid_data[1].version);
+        let stored = client.lookup_beatmap(4231121).await.unwrap();
+        assert_eq!(stored.set.title, fetched.set.title);
+        assert_eq!(server.requests().len(), 1);
+    }
+}
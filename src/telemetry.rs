@@ -0,0 +1,159 @@
+//! Tracing spans and, with the `metrics` feature, counters for every api call.
+//!
+//! Each search, beatmap info lookup and download runs inside a `sayobot_request`
+//! span carrying the endpoint, the sid, bid or keyword, the attempt count, the final
+//! HTTP status, the bytes transferred and the latency. Answers from the
+//! in-memory cache and from the SQLite store are counted apart. With the `metrics` feature
+//! the same events are reported through the [`metrics`](https://docs.rs/metrics)
+//! facade; install any recorder (e.g. a Prometheus exporter) to scrape them:
+//!
+//! | metric                              | kind      | labels             |
+//! |-------------------------------------|-----------|--------------------|
+//! | `sayobot_requests_total`            | counter   | `endpoint`         |
+//! | `sayobot_errors_total`              | counter   | `endpoint`, `kind` |
+//! | `sayobot_cache_hits_total`          | counter   | `endpoint`         |
+//! | `sayobot_store_hits_total`          | counter   | `endpoint`         |
+//! | `sayobot_download_bytes_total`      | counter   |                    |
+//! | `sayobot_request_duration_seconds`  | histogram | `endpoint`         |
+
+use std::time::Instant;
+
+use tracing::field::Empty;
+use tracing::Span;
+
+use crate::error::{Result, SayobotError};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Endpoint {
+    Search,
+    Info,
+    Download,
+}
+
+impl Endpoint {
+    pub(crate) fn as_str(self) -> &'static str {
+        match self {
+            Endpoint::Search => "search",
+            Endpoint::Info => "beatmap_info",
+            Endpoint::Download => "download",
+        }
+    }
+}
+
+/// Span of one api call; fields left empty here are filled in as the call runs.
+pub(crate) fn request_span(endpoint: Endpoint) -> Span {
+    tracing::info_span!(
+        "sayobot_request",
+        endpoint = endpoint.as_str(),
+        sid = Empty,
+        bid = Empty,
+        keyword = Empty,
+        attempt = Empty,
+        status = Empty,
+        bytes = Empty,
+        latency_ms = Empty,
+        cache_hit = Empty,
+        store_hit = Empty,
+    )
+}
+
+/// Records the current attempt and its HTTP status on the enclosing request span.
+pub(crate) fn record_attempt(attempt: u32, status: Option<u16>) {
+    let span = Span::current();
+    span.record("attempt", attempt);
+    if let Some(status) = status {
+        span.record("status", status);
+    }
+}
+
+/// Records the size of a response body on the enclosing request span.
+pub(crate) fn record_bytes(bytes: u64) {
+    Span::current().record("bytes", bytes);
+}
+
+pub(crate) fn record_cache_hit(endpoint: Endpoint) {
+    Span::current().record("cache_hit", true);
+    #[cfg(feature = "metrics")]
+    metrics::counter!("sayobot_cache_hits_total", "endpoint" => endpoint.as_str()).increment(1);
+    #[cfg(not(feature = "metrics"))]
+    let _ = endpoint;
+}
+
+/// A lookup answered from the SQLite metadata store.
+#[cfg(feature = "sqlite")]
+pub(crate) fn record_store_hit(endpoint: Endpoint) {
+    Span::current().record("store_hit", true);
+    #[cfg(feature = "metrics")]
+    metrics::counter!("sayobot_store_hits_total", "endpoint" => endpoint.as_str()).increment(1);
+    #[cfg(not(feature = "metrics"))]
+    let _ = endpoint;
+}
+
+pub(crate) fn record_download_bytes(bytes: u64) {
+    record_bytes(bytes);
+    #[cfg(feature = "metrics")]
+    metrics::counter!("sayobot_download_bytes_total").increment(bytes);
+}
+
+/// Closes out a call: latency on the span, request/error counters and the
+/// latency histogram.
+pub(crate) fn finish<T>(span: &Span, endpoint: Endpoint, started: Instant, result: &Result<T>) {
+    let elapsed = started.elapsed();
+    span.record("latency_ms", elapsed.as_millis() as u64);
+    if let Err(err) = result {
+        span.in_scope(|| tracing::debug!(error = %err, "sayobot request failed"));
+    }
+    #[cfg(feature = "metrics")]
+    {
+        let endpoint = endpoint.as_str();
+        metrics::counter!("sayobot_requests_total", "endpoint" => endpoint).increment(1);
+        metrics::histogram!("sayobot_request_duration_seconds", "endpoint" => endpoint)
+            .record(elapsed.as_secs_f64());
+        if let Err(err) = result {
+            metrics::counter!(
+                "sayobot_errors_total",
+                "endpoint" => endpoint,
+                "kind" => error_kind(err)
+            )
+            .increment(1);
+        }
+    }
+    #[cfg(not(feature = "metrics"))]
+    let _ = endpoint;
+}
+
+/// Low-cardinality label for an error.
+#[cfg_attr(not(feature = "metrics"), allow(dead_code))]
+pub(crate) fn error_kind(err: &SayobotError) -> &'static str {
+    match err {
+        SayobotError::Transport(_) => "transport",
+        SayobotError::Timeout(_) => "timeout",
+        SayobotError::HttpStatus { .. } => "http_status",
+        SayobotError::ApiStatus { .. } => "api_status",
+        SayobotError::Decode { .. } => "decode",
+        SayobotError::NotFound(_) => "not_found",
+        SayobotError::Io(_) => "io",
+        SayobotError::InvalidParams(_) => "invalid_params",
+        SayobotError::Cassette(_) => "cassette",
+        #[cfg(feature = "sqlite")]
+        SayobotError::Store(_) => "store",
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn error_kinds_are_stable_labels() {
+        assert_eq!(
+            error_kind(&SayobotError::HttpStatus { status: 503 }),
+            "http_status"
+        );
+        assert_eq!(
+            error_kind(&SayobotError::NotFound("1".to_string())),
+            "not_found"
+        );
+        assert_eq!(Endpoint::Info.as_str(), "beatmap_info");
+    }
+}
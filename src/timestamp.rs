@@ -0,0 +1,163 @@
+//! Datetime views of the unix timestamps in responses, behind the `chrono` feature.
+//!
+//! The models keep their raw `i64` fields so the wire format does not depend on
+//! the feature; accessors such as [`Data::last_updated_at`] and
+//! [`ResponseData::approved_at`] wrap them in a [`UnixTimestamp`].
+//!
+//! ```
+//! use chrono::FixedOffset;
+//! use sayobot_api_wrap::UnixTimestamp;
+//!
+//! let ranked = UnixTimestamp::from_seconds(1690531200);
+//! let beijing = FixedOffset::east_opt(8 * 3600).unwrap();
+//! assert_eq!(
+//!     ranked.format_in(&beijing, "%Y-%m-%d %H:%M").unwrap(),
+//!     "2023-07-28 16:00"
+//! );
+//! let now = UnixTimestamp::from_seconds(1690531200 + 3 * 86400).to_utc().unwrap();
+//! assert_eq!(ranked.relative_to(now), "3 days ago");
+//! ```
+//!
+//! [`Data::last_updated_at`]: crate::beatmap_response::Data::last_updated_at
+//! [`ResponseData::approved_at`]: crate::beatmap_info_v2::ResponseData::approved_at
+
+use std::fmt;
+
+use chrono::{DateTime, TimeZone, Utc};
+use serde::{Deserialize, Serialize};
+
+/// Seconds since the unix epoch, as sent by the api.
+///
+/// Orders by time and serializes as the plain integer, so it can replace an
+/// `i64` field without changing the JSON.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct UnixTimestamp(i64);
+
+impl UnixTimestamp {
+    pub fn from_seconds(seconds: i64) -> Self {
+        Self(seconds)
+    }
+    /// `None` for zero and negative values, which the api uses for "never".
+    pub fn from_api(seconds: i64) -> Option<Self> {
+        (seconds > 0).then_some(Self(seconds))
+    }
+    pub fn now() -> Self {
+        Utc::now().into()
+    }
+    pub fn seconds(self) -> i64 {
+        self.0
+    }
+    /// `None` when the value is outside chrono's range.
+    pub fn to_utc(self) -> Option<DateTime<Utc>> {
+        DateTime::from_timestamp(self.0, 0)
+    }
+    pub fn with_timezone<Tz: TimeZone>(self, tz: &Tz) -> Option<DateTime<Tz>> {
+        self.to_utc().map(|utc| utc.with_timezone(tz))
+    }
+    /// Formats in `tz` with a `strftime` pattern, see [`chrono::format::strftime`].
+    pub fn format_in<Tz>(self, tz: &Tz, pattern: &str) -> Option<String>
+    where
+        Tz: TimeZone,
+        Tz::Offset: fmt::Display,
+    {
+        self.with_timezone(tz)
+            .map(|time| time.format(pattern).to_string())
+    }
+
+    /// Coarse distance to `now`, e.g. `"3 days ago"` or `"in 2 hours"`.
+    ///
+    /// Months count as 30 days and years as 365; under a minute is `"just now"`.
+    pub fn relative_to(self, now: DateTime<Utc>) -> String {
+        let delta = now.timestamp().saturating_sub(self.0);
+        let seconds = delta.unsigned_abs();
+        if seconds < 60 {
+            return "just now".to_string();
+        }
+        let (count, unit) = [
+            (365 * 86400, "year"),
+            (30 * 86400, "month"),
+            (86400, "day"),
+            (3600, "hour"),
+            (60, "minute"),
+        ]
+        .into_iter()
+        .find(|(unit, _)| seconds >= *unit)
+        .map(|(size, unit)| (seconds / size, unit))
+        .unwrap_or((seconds / 60, "minute"));
+        let plural = if count == 1 { "" } else { "s" };
+        if delta >= 0 {
+            format!("{} {}{} ago", count, unit, plural)
+        } else {
+            format!("in {} {}{}", count, unit, plural)
+        }
+    }
+    /// [`UnixTimestamp::relative_to`] the current time.
+    pub fn ago(self) -> String {
+        self.relative_to(Utc::now())
+    }
+}
+
+impl From<i64> for UnixTimestamp {
+    fn from(seconds: i64) -> Self {
+        Self(seconds)
+    }
+}
+
+impl<Tz: TimeZone> From<DateTime<Tz>> for UnixTimestamp {
+    fn from(time: DateTime<Tz>) -> Self {
+        Self(time.timestamp())
+    }
+}
+
+/// RFC 3339 in UTC, or the raw seconds when out of range.
+impl fmt::Display for UnixTimestamp {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self.to_utc() {
+            Some(time) => write!(f, "{}", time.to_rfc3339()),
+            None => write!(f, "{}", self.0),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::beatmap_response::SearchResponse;
+
+    #[test]
+    fn relative_and_absolute_rendering() {
+        let at = |seconds: i64| UnixTimestamp::from_seconds(seconds).to_utc().unwrap();
+        let base = UnixTimestamp::from_seconds(1_690_000_000);
+        assert_eq!(base.relative_to(at(1_690_000_030)), "just now");
+        assert_eq!(base.relative_to(at(1_690_003_600)), "1 hour ago");
+        assert_eq!(
+            base.relative_to(at(1_690_000_000 + 400 * 86400)),
+            "1 year ago"
+        );
+        assert_eq!(base.relative_to(at(1_690_000_000 - 120)), "in 2 minutes");
+        assert_eq!(base.to_string(), "2023-07-22T04:26:40+00:00");
+        assert_eq!(UnixTimestamp::from_api(-1), None);
+        assert_eq!(UnixTimestamp::from(at(5)), UnixTimestamp::from_seconds(5));
+    }
+
+    #[test]
+    fn sorts_and_keeps_the_wire_format() {
+        let response: SearchResponse =
+            serde_json::from_str(include_str!("../tests/fixtures/beatmaplist.json")).unwrap();
+        let mut updated: Vec<UnixTimestamp> = response
+            .results()
+            .iter()
+            .filter_map(|set| set.last_updated_at())
+            .collect();
+        updated.sort();
+        assert_eq!(updated.first().unwrap().seconds(), 1_620_000_000);
+
+        let json = serde_json::to_string(&updated[0]).unwrap();
+        assert_eq!(json, "1620000000");
+        assert_eq!(
+            serde_json::from_str::<UnixTimestamp>(&json).unwrap(),
+            updated[0]
+        );
+    }
+}
@@ -0,0 +1,258 @@
+use std::collections::VecDeque;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use bytes::{Bytes, BytesMut};
+use futures::future::BoxFuture;
+use futures::stream::{self, BoxStream, StreamExt, TryStreamExt};
+use http::{HeaderMap, HeaderName, HeaderValue, StatusCode};
+
+use crate::error::{Result, SayobotError};
+
+/// Body of a [`TransportResponse`], delivered chunk by chunk.
+pub type BodyStream = BoxStream<'static, Result<Bytes>>;
+
+/// A GET request as handed to a [`Transport`].
+#[derive(Debug, Clone)]
+pub struct HttpRequest {
+    pub url: String,
+    pub headers: HeaderMap,
+    pub timeout: Duration,
+}
+
+impl HttpRequest {
+    pub fn new(url: &str, timeout: Duration) -> Self {
+        Self {
+            url: url.to_string(),
+            headers: HeaderMap::new(),
+            timeout,
+        }
+    }
+}
+
+pub struct TransportResponse {
+    pub status: StatusCode,
+    pub headers: HeaderMap,
+    pub body: BodyStream,
+}
+
+impl TransportResponse {
+    /// A response whose body is already in memory.
+    pub fn from_bytes(status: u16, body: impl Into<Bytes>) -> Self {
+        let body = body.into();
+        Self {
+            status: StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR),
+            headers: HeaderMap::new(),
+            body: stream::once(async move { Ok(body) }).boxed(),
+        }
+    }
+    /// Adds a header; invalid names or values are ignored.
+    pub fn with_header(mut self, name: &str, value: &str) -> Self {
+        if let (Ok(name), Ok(value)) = (
+            HeaderName::from_bytes(name.as_bytes()),
+            HeaderValue::from_str(value),
+        ) {
+            self.headers.append(name, value);
+        }
+        self
+    }
+    /// Collects the whole body.
+    pub async fn bytes(self) -> Result<Bytes> {
+        let body = self
+            .body
+            .try_fold(BytesMut::new(), |mut buf, chunk| async move {
+                buf.extend_from_slice(&chunk);
+                Ok(buf)
+            })
+            .await?;
+        Ok(body.freeze())
+    }
+}
+
+/// Sends HTTP requests on behalf of a [`crate::SayobotClient`].
+///
+/// Search, beatmap info and download requests all go through this trait, so
+/// swapping the implementation changes how every endpoint talks to the network.
+pub trait Transport: Send + Sync {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<TransportResponse>>;
+}
+
+/// The default transport, backed by a pooled [`reqwest::Client`].
+#[derive(Clone, Default)]
+pub struct ReqwestTransport {
+    client: reqwest::Client,
+}
+
+impl ReqwestTransport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+    pub fn from_client(client: reqwest::Client) -> Self {
+        Self { client }
+    }
+}
+
+impl Transport for ReqwestTransport {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<TransportResponse>> {
+        Box::pin(async move {
+            let response = self
+                .client
+                .get(&request.url)
+                .headers(request.headers)
+                .timeout(request.timeout)
+                .send()
+                .await?;
+            Ok(TransportResponse {
+                status: response.status(),
+                headers: response.headers().clone(),
+                body: response.bytes_stream().map_err(SayobotError::from).boxed(),
+            })
+        })
+    }
+}
+
+type Handler = Box<dyn Fn(&HttpRequest) -> Result<TransportResponse> + Send + Sync>;
+
+/// In-process transport for unit tests and fault injection.
+///
+/// Every request is recorded and answered by a handler, so tests can return
+/// canned bodies, error statuses or transport errors without a socket.
+pub struct FakeTransport {
+    handler: Handler,
+    requests: Mutex<Vec<HttpRequest>>,
+}
+
+impl FakeTransport {
+    pub fn new<F>(handler: F) -> Self
+    where
+        F: Fn(&HttpRequest) -> Result<TransportResponse> + Send + Sync + 'static,
+    {
+        Self {
+            handler: Box::new(handler),
+            requests: Mutex::new(Vec::new()),
+        }
+    }
+    /// Answers requests with `responses` in order, then fails every further request.
+    pub fn sequence(responses: Vec<Result<TransportResponse>>) -> Self {
+        let queue = Mutex::new(VecDeque::from(responses));
+        Self::new(move |request| {
+            queue
+                .lock()
+                .unwrap_or_else(|poisoned| poisoned.into_inner())
+                .pop_front()
+                .unwrap_or_else(|| {
+                    Err(SayobotError::Transport(
+                        format!("no fake response left for {}", request.url).into(),
+                    ))
+                })
+        })
+    }
+    pub fn requests(&self) -> Vec<HttpRequest> {
+        self.requests
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .clone()
+    }
+}
+
+impl Transport for FakeTransport {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<TransportResponse>> {
+        let response = (self.handler)(&request);
+        self.requests
+            .lock()
+            .unwrap_or_else(|poisoned| poisoned.into_inner())
+            .push(request);
+        Box::pin(async move { response })
+    }
+}
+
+impl<T: Transport + ?Sized> Transport for std::sync::Arc<T> {
+    fn send(&self, request: HttpRequest) -> BoxFuture<'_, Result<TransportResponse>> {
+        (**self).send(request)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use super::*;
+    use crate::client::SayobotClient;
+    use crate::retry::RetryPolicy;
+
+    const INFO: &str = include_str!("../tests/fixtures/beatmapinfo_2035712.json");
+
+    #[tokio::test]
+    async fn fake_transport_serves_every_endpoint() {
+        let transport = Arc::new(FakeTransport::new(|request| {
+            if request.url.contains("/v2/beatmapinfo") {
+                Ok(TransportResponse::from_bytes(200, INFO))
+            } else {
+                Ok(TransportResponse::from_bytes(200, &b"osz"[..])
+                    .with_header("content-disposition", "attachment; filename*=utf-8''1.osz"))
+            }
+        }));
+        let client = SayobotClient::builder()
+            .set_transport(transport.clone())
+            .build()
+            .unwrap();
+        let info = client
+            .beatmap_info()
+            .set_key("2035712".to_string())
+            .do_request()
+            .await
+            .unwrap();
+        assert_eq!(info.data.sid, 2035712);
+        assert_eq!(
+            transport.requests()[0].url,
+            "https://api.sayobot.cn/v2/beatmapinfo?0=2035712"
+        );
+    }
+
+    #[tokio::test]
+    async fn injected_faults_are_retried() {
+        let transport = Arc::new(FakeTransport::sequence(vec![
+            Err(SayobotError::Transport("connection reset".into())),
+            Ok(TransportResponse::from_bytes(503, "busy").with_header("retry-after", "0")),
+            Ok(TransportResponse::from_bytes(200, INFO)),
+        ]));
+        let client = SayobotClient::builder()
+            .set_transport(transport.clone())
+            .set_retry_policy(
+                RetryPolicy::new()
+                    .set_max_attempts(3)
+                    .set_backoff(Duration::ZERO, Duration::ZERO),
+            )
+            .build()
+            .unwrap();
+        let info = client
+            .beatmap_info()
+            .set_key("2035712".to_string())
+            .do_request()
+            .await
+            .unwrap();
+        assert_eq!(info.data.bids_amount, 3);
+        assert_eq!(transport.requests().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn non_retryable_status_fails_fast() {
+        let transport = Arc::new(FakeTransport::sequence(vec![Ok(
+            TransportResponse::from_bytes(404, "missing"),
+        )]));
+        let client = SayobotClient::builder()
+            .set_transport(transport.clone())
+            .build()
+            .unwrap();
+        let result = client
+            .search()
+            .set_keyword("kano".to_string())
+            .do_request()
+            .await;
+        assert!(matches!(
+            result,
+            Err(SayobotError::HttpStatus { status: 404 })
+        ));
+        assert_eq!(transport.requests().len(), 1);
+    }
+}